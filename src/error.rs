@@ -0,0 +1,142 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum FileTreeError {
+    /// Writing the output failed.
+    Io(io::Error),
+    /// Reading a directory failed. The error carries the path.
+    Walkdir(walkdir::Error),
+    /// An include or exclude pattern is not a valid glob.
+    Pattern(globset::Error),
+    /// Following a link at `path` would lead back to `ancestor`.
+    Loop { path: PathBuf, ancestor: PathBuf },
+    /// The root of the walk is not a readable directory.
+    InvalidPath {
+        path: PathBuf,
+        reason: InvalidPathReason,
+    },
+    /// A settings file, such as a theme, could not be read or parsed.
+    Config { path: PathBuf, message: String },
+    /// A tree was to be built from a stream with no entries, not even a
+    /// root.
+    Empty,
+    /// A tree was to be built from a stream that is not a single tree in
+    /// pre-order, such as one with a second root.
+    Malformed(String),
+}
+
+/// Why a root path was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidPathReason {
+    NotFound,
+    NotADirectory,
+    /// The path exists but its metadata could not be read, for example
+    /// because a parent directory is not searchable.
+    Inaccessible,
+}
+
+impl FileTreeError {
+    /// A short lowercase description, used to annotate entries inline.
+    pub fn reason(&self) -> String {
+        match self {
+            FileTreeError::Io(err) => describe(err),
+            FileTreeError::Walkdir(err) => match err.io_error() {
+                Some(io) => describe(io),
+                None => err.to_string(),
+            },
+            FileTreeError::Pattern(err) => err.to_string(),
+            FileTreeError::Loop { .. } => "filesystem loop".to_string(),
+            FileTreeError::InvalidPath { reason, .. } => reason.to_string(),
+            FileTreeError::Config { message, .. } => message.clone(),
+            FileTreeError::Empty => "no entries".to_string(),
+            FileTreeError::Malformed(message) => message.clone(),
+        }
+    }
+}
+
+/// The description of an I/O error's kind, reworded where the standard one
+/// is unclear.
+fn describe(err: &io::Error) -> String {
+    // The directory is nested deeper than the system's path length limit
+    // allows. Such directories are reported rather than walked.
+    #[cfg(unix)]
+    if err.raw_os_error() == Some(libc::ENAMETOOLONG) {
+        return "path too long".to_string();
+    }
+    err.kind().to_string()
+}
+
+impl fmt::Display for InvalidPathReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidPathReason::NotFound => f.write_str("no such file or directory"),
+            InvalidPathReason::NotADirectory => f.write_str("not a directory"),
+            InvalidPathReason::Inaccessible => f.write_str("cannot be accessed"),
+        }
+    }
+}
+
+impl fmt::Display for FileTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileTreeError::Io(err) => write!(f, "error writing output: {}", err),
+            FileTreeError::Walkdir(err) => match (err.path(), err.io_error()) {
+                (Some(path), Some(io)) => write!(f, "cannot read {}: {}", path.display(), io),
+                _ => write!(f, "{}", err),
+            },
+            FileTreeError::Pattern(err) => write!(f, "invalid pattern: {}", err),
+            FileTreeError::Loop { path, ancestor } => write!(
+                f,
+                "filesystem loop: {} leads back to {}",
+                path.display(),
+                ancestor.display()
+            ),
+            FileTreeError::InvalidPath { path, reason } => {
+                write!(f, "invalid directory path {}: {}", path.display(), reason)
+            }
+            FileTreeError::Config { path, message } => {
+                write!(f, "invalid settings in {}: {}", path.display(), message)
+            }
+            FileTreeError::Empty => f.write_str("no entries to build a tree from"),
+            FileTreeError::Malformed(message) => {
+                write!(f, "cannot build a tree from the entries: {}", message)
+            }
+        }
+    }
+}
+
+impl Error for FileTreeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FileTreeError::Io(err) => Some(err),
+            FileTreeError::Walkdir(err) => Some(err),
+            FileTreeError::Pattern(err) => Some(err),
+            FileTreeError::Loop { .. }
+            | FileTreeError::InvalidPath { .. }
+            | FileTreeError::Config { .. }
+            | FileTreeError::Empty
+            | FileTreeError::Malformed(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for FileTreeError {
+    fn from(err: io::Error) -> FileTreeError {
+        FileTreeError::Io(err)
+    }
+}
+
+impl From<walkdir::Error> for FileTreeError {
+    fn from(err: walkdir::Error) -> FileTreeError {
+        FileTreeError::Walkdir(err)
+    }
+}
+
+impl From<globset::Error> for FileTreeError {
+    fn from(err: globset::Error) -> FileTreeError {
+        FileTreeError::Pattern(err)
+    }
+}
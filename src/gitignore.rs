@@ -0,0 +1,157 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder, Glob};
+use ignore::Match;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Ignore rules in effect for one directory, chained to those of its parent.
+///
+/// Precedence follows ripgrep: `.ignore` files beat `.gitignore` files, which
+/// beat the repository's `.git/info/exclude`, which beats the user's global
+/// excludes file. Within each kind the deepest directory wins.
+pub(crate) struct IgnoreRules {
+    dir: PathBuf,
+    parent: Option<Arc<IgnoreRules>>,
+    dot_ignore: Gitignore,
+    gitignore: Gitignore,
+    git_exclude: Option<Arc<Gitignore>>,
+    global: Arc<Gitignore>,
+}
+
+impl IgnoreRules {
+    /// Rules for `root`, including the ignore files of its ancestors up to the
+    /// enclosing git repository. `root` must be absolute.
+    pub(crate) fn for_root(root: &Path) -> Arc<IgnoreRules> {
+        let (global, _) = Gitignore::global();
+        let global = Arc::new(global);
+
+        let repo = root.ancestors().position(|dir| dir.join(".git").exists());
+        let dirs: Vec<&Path> = root.ancestors().take(repo.unwrap_or(0) + 1).collect();
+
+        let mut rules: Option<Arc<IgnoreRules>> = None;
+        for dir in dirs.into_iter().rev() {
+            rules = Some(IgnoreRules::new(rules, dir.to_path_buf(), global.clone()));
+        }
+        rules.unwrap()
+    }
+
+    /// Rules for the child directory `name`.
+    pub(crate) fn descend(self: &Arc<Self>, name: &Path) -> Arc<IgnoreRules> {
+        IgnoreRules::new(Some(self.clone()), self.dir.join(name), self.global.clone())
+    }
+
+    fn new(
+        parent: Option<Arc<IgnoreRules>>,
+        dir: PathBuf,
+        global: Arc<Gitignore>,
+    ) -> Arc<IgnoreRules> {
+        let git_dir = dir.join(".git");
+        let git_exclude = if git_dir.is_dir() {
+            Some(Arc::new(load(&dir, &git_dir.join("info").join("exclude"))))
+        } else {
+            parent.as_ref().and_then(|p| p.git_exclude.clone())
+        };
+
+        Arc::new(IgnoreRules {
+            dot_ignore: load(&dir, &dir.join(".ignore")),
+            gitignore: load(&dir, &dir.join(".gitignore")),
+            dir,
+            parent,
+            git_exclude,
+            global,
+        })
+    }
+
+    /// Whether the child `name` of this directory is ignored.
+    pub(crate) fn is_ignored(&self, name: &Path, is_dir: bool) -> bool {
+        let path = self.dir.join(name);
+        let levels = || iter::successors(Some(self), |rules| rules.parent.as_deref());
+
+        first_match(levels().map(|rules| rules.dot_ignore.matched(&path, is_dir)))
+            .or(first_match(
+                levels().map(|rules| rules.gitignore.matched(&path, is_dir)),
+            ))
+            .or(self
+                .git_exclude
+                .as_ref()
+                .map_or(Match::None, |exclude| exclude.matched(&path, is_dir)))
+            .or(self.global.matched(&path, is_dir))
+            .is_ignore()
+    }
+}
+
+fn first_match<'a>(mut matches: impl Iterator<Item = Match<&'a Glob>>) -> Match<&'a Glob> {
+    matches.find(|m| !m.is_none()).unwrap_or(Match::None)
+}
+
+fn load(dir: &Path, file: &Path) -> Gitignore {
+    if !file.is_file() {
+        return Gitignore::empty();
+    }
+
+    let mut builder = GitignoreBuilder::new(dir);
+    builder.add(file);
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A fresh directory under the system temp dir, removed on drop.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Scratch {
+            let dir =
+                std::env::temp_dir().join(format!("treewalker-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Scratch(dir.canonicalize().unwrap())
+        }
+
+        fn write(&self, name: &str, text: &str) {
+            fs::write(self.0.join(name), text).unwrap();
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn dot_ignore_negation_beats_gitignore() {
+        let dir = Scratch::new("ignore-negation");
+        dir.write(".gitignore", "*.log\n");
+        dir.write(".ignore", "!keep.log\n");
+
+        let rules = IgnoreRules::for_root(&dir.0);
+        assert!(rules.is_ignored(Path::new("other.log"), false));
+        assert!(!rules.is_ignored(Path::new("keep.log"), false));
+    }
+
+    #[test]
+    fn gitignore_negation_does_not_beat_dot_ignore() {
+        let dir = Scratch::new("gitignore-negation");
+        dir.write(".ignore", "*.log\n");
+        dir.write(".gitignore", "!keep.log\n");
+
+        let rules = IgnoreRules::for_root(&dir.0);
+        assert!(rules.is_ignored(Path::new("keep.log"), false));
+    }
+
+    #[test]
+    fn deeper_gitignore_wins() {
+        let dir = Scratch::new("gitignore-depth");
+        fs::create_dir(dir.0.join("sub")).unwrap();
+        dir.write(".gitignore", "*.log\n");
+        dir.write("sub/.gitignore", "!keep.log\n");
+
+        let rules = IgnoreRules::for_root(&dir.0).descend(Path::new("sub"));
+        assert!(!rules.is_ignored(Path::new("keep.log"), false));
+        assert!(rules.is_ignored(Path::new("other.log"), false));
+    }
+}
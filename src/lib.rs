@@ -0,0 +1,23 @@
+//! Directory tree walking and rendering.
+//!
+//! A [`TreeWalker`] is configured with the builder methods and then either
+//! streamed with [`TreeWalker::walk`] or collected into a [`Node`] tree with
+//! [`TreeWalker::build`]. The [`render`] module turns a walk into output.
+
+mod error;
+mod gitignore;
+mod parallel;
+mod patterns;
+pub mod render;
+mod size;
+mod sort;
+mod summary;
+mod tree;
+mod walker;
+
+pub use error::{FileTreeError, InvalidPathReason};
+pub use size::SizeFormat;
+pub use sort::{DirOrder, SortKey};
+pub use summary::Summary;
+pub use tree::Node;
+pub use walker::{Entry, EntryKind, Link, RootStyle, TreeWalker, Walk};
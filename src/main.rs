@@ -1,113 +1,558 @@
-use clap::{Arg, Command};
-use std::io::{self};
-use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
-
-#[derive(Debug)]
-enum FileTreeError {
-    Io(io::Error),
-    Walkdir(walkdir::Error),
-    InvalidPath,
-}
+use clap::{Arg, ArgMatches, Command};
+use std::env;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use treewalker::render::color::LsColors;
+use treewalker::render::graph::{
+    print_graph, print_graph_end, print_graph_start, GraphFormat, GraphOptions,
+};
+use treewalker::render::html::{print_html, print_html_end, print_html_start, HtmlOptions};
+use treewalker::render::json::{print_json, print_json_list};
+use treewalker::render::markdown::{print_markdown, MarkdownOptions, MarkdownStyle};
+use treewalker::render::ndjson::print_ndjson;
+use treewalker::render::quote::QuotingStyle;
+use treewalker::render::text::{print_report, print_tree, TextOptions};
+use treewalker::render::theme::{Charset, Theme, MAX_INDENT};
+use treewalker::render::xml::{print_xml, print_xml_end, print_xml_start, XmlOptions};
+use treewalker::{
+    DirOrder, Entry, FileTreeError, Node, RootStyle, SizeFormat, SortKey, Summary, TreeWalker,
+};
 
-impl From<io::Error> for FileTreeError {
-    fn from(err: io::Error) -> FileTreeError {
-        FileTreeError::Io(err)
-    }
-}
+/// Some directories could not be read; the rest of the tree was printed.
+const EXIT_PARTIAL: i32 = 1;
+/// Bad command line arguments or patterns. Matches clap's own exit code.
+const EXIT_USAGE: i32 = 2;
+/// A root path is missing or not a directory.
+const EXIT_INVALID_PATH: i32 = 3;
+/// The output could not be produced or written.
+const EXIT_OUTPUT: i32 = 4;
 
-impl From<walkdir::Error> for FileTreeError {
-    fn from(err: walkdir::Error) -> FileTreeError {
-        FileTreeError::Walkdir(err)
-    }
+fn main() {
+    let matches = Command::new("Treewalker")
+        .after_help(
+            "Exit status:\n  \
+             0  the whole tree was printed\n  \
+             1  some directories could not be read\n  \
+             2  usage error\n  \
+             3  a path is missing or not a directory\n  \
+             4  the output could not be produced or written",
+        )
+        .arg(
+            Arg::new("path")
+                .help("The directories to print, one tree each")
+                .num_args(1..)
+                .default_value(".")
+                .value_parser(clap::value_parser!(PathBuf))
+                .index(1),
+        )
+        .arg(
+            Arg::new("root")
+                .long("root")
+                .value_name("STYLE")
+                .help("How to show each root: as given, canonical, or relative to the current directory")
+                .value_parser(["given", "canonical", "relative"])
+                .default_value("given"),
+        )
+        .arg(
+            Arg::new("ignore-hidden")
+                .long("ignore-hidden")
+                .help("Ignore files and folders that start with a '.'")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("gitignore")
+                .long("gitignore")
+                .help("Skip files ignored by .gitignore, .ignore and git excludes")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("follow-symlinks")
+                .long("follow-symlinks")
+                .help("Descend into symbolic links to directories")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .value_name("GLOB")
+                .help("Only show files matching the pattern (repeatable)")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .value_name("GLOB")
+                .help("Skip files and directories matching the pattern (repeatable)")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("max-depth")
+                .long("max-depth")
+                .value_name("N")
+                .help("Descend at most N levels below the root")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .value_name("N")
+                .help("Read directories on N threads, at most 64; 0 uses one per CPU")
+                .value_parser(clap::value_parser!(u16).range(0..=64))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("size")
+                .long("size")
+                .help("Show disk usage of files and directory totals in bytes, as du counts it")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("human")
+                .long("human")
+                .value_name("UNITS")
+                .help("Show sizes in human-readable units, powers of 1024 (iec) or 1000 (si)")
+                .value_parser(["iec", "si"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("iec"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .help(
+                    "Order siblings by the given key; size compares a directory's own size, \
+                     not its total",
+                )
+                .value_parser([
+                    "name",
+                    "natural",
+                    "size",
+                    "mtime",
+                    "ctime",
+                    "extension",
+                    "none",
+                ])
+                .default_value("name"),
+        )
+        .arg(
+            Arg::new("reverse")
+                .long("reverse")
+                .help("Reverse the sort order")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dirs-first")
+                .long("dirs-first")
+                .help("List directories before files (default)")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["dirs-last", "mixed"]),
+        )
+        .arg(
+            Arg::new("dirs-last")
+                .long("dirs-last")
+                .help("List directories after files")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("mixed"),
+        )
+        .arg(
+            Arg::new("mixed")
+                .long("mixed")
+                .help("Sort directories together with files")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("ignore-case")
+                .long("ignore-case")
+                .help("Sort names case-insensitively")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .value_name("WHEN")
+                .help(
+                    "Color names using LS_COLORS; auto colors only on a terminal without NO_COLOR",
+                )
+                .value_parser(["auto", "always", "never"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_value("auto")
+                .default_missing_value("always"),
+        )
+        .arg(
+            Arg::new("quoting-style")
+                .long("quoting-style")
+                .value_name("STYLE")
+                .help("How to write names: literal, escape, shell or c [default: escape on a terminal, literal otherwise]")
+                .value_parser(["literal", "escape", "shell", "c"])
+                .overrides_with("literal"),
+        )
+        .arg(
+            Arg::new("literal")
+                .long("literal")
+                .help("Write names as raw bytes, like --quoting-style=literal")
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("quoting-style"),
+        )
+        .arg(
+            Arg::new("charset")
+                .long("charset")
+                .help("Characters to draw branches with")
+                .value_parser(["ascii", "unicode", "rounded", "heavy", "double"])
+                .default_value("unicode"),
+        )
+        .arg(
+            Arg::new("indent")
+                .long("indent")
+                .value_name("N")
+                .help("Columns per level of the tree, from 2 to 64 [default: 4]")
+                .value_parser(clap::value_parser!(u16).range(2..=i64::from(MAX_INDENT))),
+        )
+        .arg(
+            Arg::new("theme")
+                .long("theme")
+                .value_name("FILE")
+                .help("Read branch characters and indent from a file of key = value lines")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("noreport")
+                .long("noreport")
+                .help("Omit the directory and file counts at the end of the tree")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Output format")
+                .value_parser([
+                    "text", "json", "ndjson", "markdown", "html", "dot", "mermaid", "xml",
+                ])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("markdown-style")
+                .long("markdown-style")
+                .value_name("STYLE")
+                .help("With --format markdown, a fenced text tree or a nested bullet list")
+                .value_parser(["fence", "list"])
+                .default_value("fence"),
+        )
+        .arg(
+            Arg::new("graph-nodes")
+                .long("graph-nodes")
+                .value_name("NODES")
+                .help("With --format dot or mermaid, draw every entry or only directories")
+                .value_parser(["all", "dirs"])
+                .default_value("all"),
+        )
+        .arg(
+            Arg::new("links")
+                .long("links")
+                .help("Link each name to its path, in formats that support it")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("link-base")
+                .long("link-base")
+                .value_name("DIR")
+                .help("Make links relative to DIR, where the output will be saved [default: .]")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .get_matches();
+
+    let code = match run(&matches) {
+        Ok(0) => return,
+        Ok(code) => code,
+        // The reader went away, as with `| head`; there is nobody left to
+        // tell and nothing wrong with the tree.
+        Err(FileTreeError::Io(err)) if err.kind() == io::ErrorKind::BrokenPipe => return,
+        Err(err) => {
+            eprintln!("treewalker: {}", err);
+            match err {
+                // A walk always yields its root or an error, so a tree that
+                // cannot be built from one is a fault in producing the output.
+                FileTreeError::Io(_) | FileTreeError::Empty | FileTreeError::Malformed(_) => {
+                    EXIT_OUTPUT
+                }
+                FileTreeError::Walkdir(_) | FileTreeError::Loop { .. } => EXIT_PARTIAL,
+                FileTreeError::Pattern(_) | FileTreeError::Config { .. } => EXIT_USAGE,
+                FileTreeError::InvalidPath { .. } => EXIT_INVALID_PATH,
+            }
+        }
+    };
+
+    process::exit(code);
 }
 
-fn get_dir_entries(path: &Path, ignore_hidden: bool) -> Result<Vec<PathBuf>, FileTreeError> {
-    let mut entries: Vec<PathBuf> = vec![];
+/// Print a tree for each root path, returning the exit code. A root that is
+/// not a directory is reported and skipped; only output errors stop early.
+fn run(matches: &ArgMatches) -> Result<i32, FileTreeError> {
+    let ignore_hidden = matches.get_flag("ignore-hidden");
 
-    for entry in WalkDir::new(path).min_depth(1).max_depth(1) {
-        let entry = entry?;
+    let gitignore = matches.get_flag("gitignore");
 
-        if ignore_hidden && entry.file_name().to_string_lossy().starts_with('.') {
-            continue;
-        }
+    let format = matches.get_one::<String>("format").unwrap();
 
-        entries.push(entry.path().to_path_buf());
-    }
+    let size = match matches.get_one::<String>("human").map(String::as_str) {
+        Some("si") => Some(SizeFormat::Si),
+        Some(_) => Some(SizeFormat::Iec),
+        None if matches.get_flag("size") => Some(SizeFormat::Bytes),
+        None => None,
+    };
 
-    entries.sort_by(|a, b| {
-        let a_is_dir = a.is_dir();
-        let b_is_dir = b.is_dir();
-        if a_is_dir == b_is_dir {
-            a.cmp(b)
-        } else if a_is_dir {
-            std::cmp::Ordering::Less
-        } else {
-            std::cmp::Ordering::Greater
-        }
-    });
+    let colors = match matches.get_one::<String>("color").unwrap().as_str() {
+        "always" => true,
+        "never" => false,
+        _ => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+    };
 
-    Ok(entries)
-}
+    // Control characters in names could move the cursor or worse, so they
+    // are escaped unless the output is going somewhere other than a terminal.
+    let quoting = match matches
+        .get_one::<String>("quoting-style")
+        .map(String::as_str)
+    {
+        _ if matches.get_flag("literal") => QuotingStyle::Literal,
+        Some("literal") => QuotingStyle::Literal,
+        Some("shell") => QuotingStyle::Shell,
+        Some("c") => QuotingStyle::C,
+        Some(_) => QuotingStyle::Escape,
+        None if io::stdout().is_terminal() => QuotingStyle::Escape,
+        None => QuotingStyle::Literal,
+    };
 
-fn print_tree(path: &Path, prefix: &str, ignore_hidden: bool) -> Result<(), FileTreeError> {
-    if !path.is_dir() {
-        return Err(FileTreeError::InvalidPath);
+    let charset = matches.get_one::<String>("charset").unwrap();
+    let mut theme = Theme::new(charset.parse().unwrap_or(Charset::Unicode));
+    if let Some(file) = matches.get_one::<PathBuf>("theme") {
+        theme = theme.load(file)?;
+    }
+    if let Some(&indent) = matches.get_one::<u16>("indent") {
+        theme.indent = usize::from(indent);
     }
 
-    let entries = get_dir_entries(path, ignore_hidden)?;
+    let options = TextOptions {
+        size,
+        colors: colors.then(LsColors::from_env),
+        quoting,
+        theme,
+    };
 
-    for (i, entry) in entries.iter().enumerate() {
-        let is_last = i == entries.len() - 1;
-        let file_name = entry.file_name().unwrap().to_string_lossy();
+    let markdown = MarkdownOptions {
+        style: match matches
+            .get_one::<String>("markdown-style")
+            .unwrap()
+            .as_str()
+        {
+            "list" => MarkdownStyle::List,
+            _ => MarkdownStyle::Fence,
+        },
+        links: matches.get_flag("links"),
+        link_base: matches.get_one::<PathBuf>("link-base").cloned(),
+        text: options.clone(),
+    };
 
-        let new_prefix = if is_last {
-            format!("{}└── ", prefix)
-        } else {
-            format!("{}├── ", prefix)
-        };
+    let html = HtmlOptions {
+        size,
+        links: matches.get_flag("links"),
+        link_base: matches.get_one::<PathBuf>("link-base").cloned(),
+    };
 
-        let continuation_prefix = if is_last {
-            format!("{}    ", prefix)
-        } else {
-            format!("{}│   ", prefix)
-        };
+    let xml = XmlOptions {
+        size: size.is_some(),
+    };
+
+    let graph = GraphOptions {
+        format: match format.as_str() {
+            "mermaid" => GraphFormat::Mermaid,
+            _ => GraphFormat::Dot,
+        },
+        dirs_only: matches.get_one::<String>("graph-nodes").unwrap() == "dirs",
+    };
 
-        if entry.is_dir() {
-            println!("{}{}/", new_prefix, file_name);
-            print_tree(entry, &continuation_prefix, ignore_hidden)?;
-        } else {
-            println!("{}{}", new_prefix, file_name);
+    let sort = match matches.get_one::<String>("sort").unwrap().as_str() {
+        "natural" => SortKey::Natural,
+        "size" => SortKey::Size,
+        "mtime" => SortKey::Mtime,
+        "ctime" => SortKey::Ctime,
+        "extension" => SortKey::Extension,
+        "none" => SortKey::None,
+        _ => SortKey::Name,
+    };
+
+    let dirs = if matches.get_flag("dirs-last") {
+        DirOrder::Last
+    } else if matches.get_flag("mixed") {
+        DirOrder::Mixed
+    } else {
+        DirOrder::First
+    };
+
+    // Directory totals are only known once a subtree has been walked, so
+    // sizes are rendered from the collected tree instead of the live walk,
+    // except in NDJSON, which writes each total after its subtree.
+    let totals = size.is_some();
+
+    let root_style = match matches.get_one::<String>("root").unwrap().as_str() {
+        "canonical" => RootStyle::Canonical,
+        "relative" => RootStyle::Relative,
+        _ => RootStyle::Given,
+    };
+
+    let threads = match *matches.get_one::<u16>("threads").unwrap() {
+        0 => thread::available_parallelism().map_or(1, usize::from),
+        n => usize::from(n),
+    };
+
+    let roots: Vec<&PathBuf> = matches.get_many::<PathBuf>("path").unwrap().collect();
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut summary = Summary::default();
+    let mut trees: Vec<Node> = vec![];
+    let mut failures: Vec<(PathBuf, Arc<FileTreeError>)> = vec![];
+    let mut invalid = false;
+    let mut started = false;
+
+    for (number, root) in roots.iter().enumerate() {
+        let mut walker = TreeWalker::new(root)
+            .root_style(root_style)
+            .threads(threads)
+            .ignore_hidden(ignore_hidden)
+            .gitignore(gitignore)
+            .follow_links(matches.get_flag("follow-symlinks"))
+            .sizes(totals)
+            .sort(sort)
+            .dirs(dirs)
+            .reverse(matches.get_flag("reverse"))
+            .ignore_case(matches.get_flag("ignore-case"));
+
+        if let Some(&depth) = matches.get_one::<usize>("max-depth") {
+            walker = walker.max_depth(depth);
         }
-    }
 
-    Ok(())
-}
+        for pattern in matches.get_many::<String>("include").into_iter().flatten() {
+            walker = walker.include(pattern)?;
+        }
 
-fn main() {
-    let matches = Command::new("Treewalker")
-        .arg(
-            Arg::new("path")
-                .help("The path to the directory")
-                .required(true)
-                .index(1),
-        )
-        .arg(
-            Arg::new("ignore-hidden")
-                .long("ignore-hidden")
-                .help("Ignore files and folders that start with a '.'")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .get_matches();
+        for pattern in matches.get_many::<String>("exclude").into_iter().flatten() {
+            walker = walker.exclude(pattern)?;
+        }
 
-    let path = matches.get_one::<String>("path").expect("Path is required");
+        // Documents are started once the patterns are known to be good, so
+        // that a usage error leaves nothing half-written behind.
+        if !started {
+            match format.as_str() {
+                "html" => {
+                    let title: Vec<String> = roots
+                        .iter()
+                        .map(|root| root.display().to_string())
+                        .collect();
+                    print_html_start(&mut out, &title.join(" "))?;
+                }
+                "dot" | "mermaid" => print_graph_start(&mut out, &graph)?,
+                "xml" => print_xml_start(&mut out)?,
+                _ => {}
+            }
+            started = true;
+        }
 
-    let ignore_hidden = matches.get_flag("ignore-hidden");
+        let entries = walker.walk().inspect(|entry| {
+            if let Ok(Entry {
+                path,
+                error: Some(err),
+                ..
+            }) = entry
+            {
+                failures.push((path.clone(), err.clone()));
+            }
+        });
+
+        let result = match format.as_str() {
+            "json" => Node::from_entries(entries).map(|tree| trees.push(tree)),
+            "ndjson" => print_ndjson(&mut out, entries),
+            "markdown" if totals => Node::from_entries(entries)
+                .and_then(|tree| print_markdown(&mut out, tree.entries(), &markdown))
+                .map(|tree| summary.merge(&tree)),
+            "markdown" => {
+                print_markdown(&mut out, entries, &markdown).map(|tree| summary.merge(&tree))
+            }
+            "dot" | "mermaid" => {
+                // Node IDs only need telling apart when there are several
+                // trees in the diagram.
+                let scope = match roots.len() {
+                    1 => String::new(),
+                    _ => format!("{}:", number + 1),
+                };
+                print_graph(&mut out, entries, &graph, &scope)
+            }
+            "xml" if totals => Node::from_entries(entries)
+                .and_then(|tree| print_xml(&mut out, tree.entries(), &xml))
+                .map(|tree| summary.merge(&tree)),
+            "xml" => print_xml(&mut out, entries, &xml).map(|tree| summary.merge(&tree)),
+            "html" if totals => Node::from_entries(entries)
+                .and_then(|tree| print_html(&mut out, tree.entries(), &html))
+                .map(|tree| summary.merge(&tree)),
+            "html" => print_html(&mut out, entries, &html).map(|tree| summary.merge(&tree)),
+            _ if totals => Node::from_entries(entries)
+                .and_then(|tree| print_tree(&mut out, tree.entries(), &options))
+                .map(|tree| summary.merge(&tree)),
+            _ => print_tree(&mut out, entries, &options).map(|tree| summary.merge(&tree)),
+        };
+
+        match result {
+            Err(err @ FileTreeError::InvalidPath { .. }) => {
+                // Keep the message in place relative to the trees around it.
+                out.flush()?;
+                eprintln!("treewalker: {}", err);
+                invalid = true;
+            }
+            result => result?,
+        }
+    }
+
+    match format.as_str() {
+        "json" if roots.len() == 1 => {
+            if let Some(tree) = trees.first() {
+                print_json(&mut out, tree)?;
+            }
+        }
+        "json" => print_json_list(&mut out, &trees)?,
+        "ndjson" => {}
+        "dot" | "mermaid" => print_graph_end(&mut out, &graph)?,
+        "xml" => {
+            let report = (!matches.get_flag("noreport")).then_some(&summary);
+            print_xml_end(&mut out, report, &xml)?;
+        }
+        "html" => {
+            let report = (!matches.get_flag("noreport")).then_some(&summary);
+            print_html_end(&mut out, report, &html)?;
+        }
+        _ => {
+            if !matches.get_flag("noreport") {
+                print_report(&mut out, &summary, &options)?;
+            }
+        }
+    }
+    out.flush()?;
 
-    match print_tree(Path::new(path), "", ignore_hidden) {
-        Ok(_) => {}
-        Err(FileTreeError::Io(err)) => eprintln!("Error reading the directory: {}", err),
-        Err(FileTreeError::Walkdir(err)) => eprintln!("Error walking the directory: {}", err),
-        Err(FileTreeError::InvalidPath) => eprintln!("Invalid directory path: {}", path),
+    if !failures.is_empty() {
+        match failures.len() {
+            1 => eprintln!("1 directory was skipped:"),
+            n => eprintln!("{} directories were skipped:", n),
+        }
+        for (path, err) in &failures {
+            eprintln!("  {}: {}", path.display(), err.reason());
+        }
     }
-}
\ No newline at end of file
+
+    Ok(if invalid {
+        EXIT_INVALID_PATH
+    } else if !failures.is_empty() {
+        EXIT_PARTIAL
+    } else {
+        0
+    })
+}
@@ -0,0 +1,395 @@
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::FileTreeError;
+use crate::gitignore::IgnoreRules;
+use crate::walker::{Child, FileId, Probes, TreeWalker};
+
+/// The most threads a pool will start.
+pub(crate) const MAX_THREADS: usize = 64;
+
+/// Listing a directory needs little stack, and a smaller reservation than
+/// the default lets more threads start under a tight memory limit.
+const STACK_SIZE: usize = 512 * 1024;
+
+/// How many listings may be read ahead of the walk before the threads wait
+/// for it to catch up.
+const LOOKAHEAD: usize = 256;
+
+/// A directory's sorted children, read ahead of the walk, with the ignore
+/// rules they were filtered by.
+pub(crate) struct Listing {
+    pub(crate) rules: Option<Arc<IgnoreRules>>,
+    pub(crate) children: Result<Vec<Child>, FileTreeError>,
+}
+
+struct Job {
+    dir: PathBuf,
+    depth: usize,
+    rules: Option<Arc<IgnoreRules>>,
+    /// This directory and those above it, to keep followed links from
+    /// looping.
+    ancestors: Arc<Vec<FileId>>,
+}
+
+enum Slot {
+    Queued,
+    Running,
+    Done(Listing),
+    /// A sort or filter callback panicked while listing the directory.
+    Panicked(Box<dyn Any + Send>),
+}
+
+#[derive(Default)]
+struct Slots {
+    slots: HashMap<PathBuf, Slot>,
+    /// Listings finished and not yet taken.
+    done: usize,
+}
+
+struct Shared {
+    walker: Arc<TreeWalker>,
+    probes: Arc<Probes>,
+    injector: Injector<Job>,
+    stealers: Vec<Stealer<Job>>,
+    slots: Mutex<Slots>,
+    /// Signalled whenever a listing is finished or taken.
+    ready: Condvar,
+    stop: AtomicBool,
+}
+
+/// Reads directories on a pool of threads ahead of a [`crate::Walk`].
+///
+/// Listing a directory queues its subdirectories, so the pool works through
+/// the tree on its own while the walk takes the listings it needs in display
+/// order. Each thread works depth-first on its own queue and steals from the
+/// others when it runs dry, which keeps the reads close to where the walk is.
+/// At most [`LOOKAHEAD`] listings are held for the walk at a time, and a
+/// directory the pool has not started on yet is read by the walk itself
+/// rather than waited for.
+pub(crate) struct Prefetch {
+    shared: Arc<Shared>,
+}
+
+impl Prefetch {
+    /// Start up to `threads` threads, or `None` if none could be started.
+    pub(crate) fn new(
+        walker: Arc<TreeWalker>,
+        threads: usize,
+        probes: Arc<Probes>,
+    ) -> Option<Prefetch> {
+        let workers: Vec<Worker<Job>> = (0..threads).map(|_| Worker::new_lifo()).collect();
+        let shared = Arc::new(Shared {
+            walker,
+            probes,
+            injector: Injector::new(),
+            stealers: workers.iter().map(Worker::stealer).collect(),
+            slots: Mutex::new(Slots::default()),
+            ready: Condvar::new(),
+            stop: AtomicBool::new(false),
+        });
+
+        let mut started = 0;
+        for worker in workers {
+            let pool = shared.clone();
+            let spawned = thread::Builder::new()
+                .name("treewalker-read".to_string())
+                .stack_size(STACK_SIZE)
+                .spawn(move || pool.run(worker));
+            // Out of threads or memory: carry on with those already running.
+            if spawned.is_err() {
+                break;
+            }
+            started += 1;
+        }
+
+        if started == 0 {
+            return None;
+        }
+        Some(Prefetch { shared })
+    }
+
+    /// The listing of `dir`, waiting for it if it is being read. `None`
+    /// means the pool has not started on it, and the caller should read it
+    /// and hand its children over with [`Prefetch::queue`]. A panic while
+    /// reading it is passed on to the caller.
+    pub(crate) fn take(&self, dir: &Path) -> Option<Listing> {
+        let mut slots = self.shared.slots.lock().unwrap();
+        loop {
+            match slots.slots.remove(dir) {
+                Some(Slot::Done(listing)) => {
+                    slots.done -= 1;
+                    self.shared.ready.notify_all();
+                    return Some(listing);
+                }
+                Some(Slot::Running) => {
+                    slots.slots.insert(dir.to_path_buf(), Slot::Running);
+                    slots = self.shared.ready.wait(slots).unwrap();
+                }
+                Some(Slot::Panicked(payload)) => {
+                    drop(slots);
+                    panic::resume_unwind(payload);
+                }
+                // Removing a queued slot keeps the pool from reading it too.
+                Some(Slot::Queued) | None => return None,
+            }
+        }
+    }
+
+    /// Queue the subdirectories of `dir`, which was read by the walk at
+    /// `depth`, with `ancestors` being the identities of the directories
+    /// from the root down to `dir`.
+    pub(crate) fn queue(
+        &self,
+        depth: usize,
+        rules: Option<&Arc<IgnoreRules>>,
+        ancestors: Vec<FileId>,
+        children: &[Child],
+    ) {
+        let ancestors = Arc::new(ancestors);
+        self.shared
+            .queue(depth, rules, &ancestors, children, |job| {
+                self.shared.injector.push(job)
+            });
+    }
+}
+
+impl Drop for Prefetch {
+    fn drop(&mut self) {
+        // Held so that a thread about to wait cannot miss the signal.
+        let _slots = self.shared.slots.lock().unwrap();
+        self.shared.stop.store(true, Ordering::Relaxed);
+        self.shared.ready.notify_all();
+    }
+}
+
+impl Shared {
+    fn run(&self, local: Worker<Job>) {
+        while !self.stop.load(Ordering::Relaxed) {
+            {
+                let mut slots = self.slots.lock().unwrap();
+                while slots.done >= LOOKAHEAD && !self.stop.load(Ordering::Relaxed) {
+                    slots = self.ready.wait(slots).unwrap();
+                }
+            }
+
+            match self.find(&local) {
+                Some(job) => self.list(job, &local),
+                None => {
+                    // Nothing to steal; wait for another thread to finish a
+                    // listing, which may have queued more work.
+                    let slots = self.slots.lock().unwrap();
+                    let _ = self.ready.wait_timeout(slots, Duration::from_millis(10));
+                }
+            }
+        }
+    }
+
+    fn find(&self, local: &Worker<Job>) -> Option<Job> {
+        local.pop().or_else(|| {
+            std::iter::repeat_with(|| {
+                self.injector
+                    .steal_batch_and_pop(local)
+                    .or_else(|| self.stealers.iter().map(Stealer::steal).collect())
+            })
+            .find(|steal| !steal.is_retry())
+            .and_then(Steal::success)
+        })
+    }
+
+    /// Read one directory, unless the walk got to it first, and queue the
+    /// subdirectories the walk will descend into.
+    fn list(&self, job: Job, local: &Worker<Job>) {
+        match self.slots.lock().unwrap().slots.get_mut(&job.dir) {
+            Some(slot @ Slot::Queued) => *slot = Slot::Running,
+            _ => return,
+        }
+
+        // Sort and filter callbacks are the caller's code; a panic in one
+        // is handed to the walk rather than leaving it waiting.
+        let read = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.walker
+                .get_dir_entries(&job.dir, job.rules.as_ref(), &self.probes)
+        }));
+
+        let slot = match read {
+            Ok(children) => {
+                if let Ok(children) = &children {
+                    self.queue(
+                        job.depth,
+                        job.rules.as_ref(),
+                        &job.ancestors,
+                        children,
+                        |job| local.push(job),
+                    );
+                }
+                Slot::Done(Listing {
+                    rules: job.rules,
+                    children,
+                })
+            }
+            Err(payload) => Slot::Panicked(payload),
+        };
+
+        let mut slots = self.slots.lock().unwrap();
+        if matches!(slot, Slot::Done(_)) {
+            slots.done += 1;
+        }
+        slots.slots.insert(job.dir, slot);
+        self.ready.notify_all();
+    }
+
+    /// Queue the subdirectories among `children` of a directory at `depth`
+    /// that the walk will descend into, last first so that the first is
+    /// read next.
+    fn queue(
+        &self,
+        depth: usize,
+        rules: Option<&Arc<IgnoreRules>>,
+        ancestors: &Arc<Vec<FileId>>,
+        children: &[Child],
+        mut push: impl FnMut(Job),
+    ) {
+        let walker = &self.walker;
+        let depth = depth + 1;
+        if walker.max_depth.is_some_and(|max| depth >= max) {
+            return;
+        }
+
+        for child in children.iter().rev() {
+            if !child.is_dir() {
+                continue;
+            }
+
+            let mut ancestors = ancestors.clone();
+            if walker.follow_links {
+                match child.dir_id() {
+                    Some(id) if ancestors.contains(&id) => continue,
+                    Some(id) => Arc::make_mut(&mut ancestors).push(id),
+                    None => {}
+                }
+            }
+
+            let rules = rules
+                .map(|rules| rules.descend(Path::new(child.path.file_name().unwrap_or_default())));
+
+            self.slots
+                .lock()
+                .unwrap()
+                .slots
+                .insert(child.path.clone(), Slot::Queued);
+            push(Job {
+                dir: child.path.clone(),
+                depth,
+                rules,
+                ancestors,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TreeWalker;
+    use std::fs;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::path::Path;
+
+    /// A tree `levels` deep with `fanout` directories and a couple of files
+    /// in each, where each `d1` also links back up and across to a sibling,
+    /// and only the deepest `d2`s hold a Markdown file.
+    fn generate(dir: &Path, levels: usize, fanout: usize) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        fs::write(dir.join("main.rs"), "").unwrap();
+        if levels == 0 {
+            if dir.ends_with("d2") {
+                fs::write(dir.join("found.md"), "").unwrap();
+            }
+            return;
+        }
+        for i in 0..fanout {
+            generate(&dir.join(format!("d{}", i)), levels - 1, fanout);
+        }
+        #[cfg(unix)]
+        if dir.ends_with("d1") {
+            std::os::unix::fs::symlink("..", dir.join("up")).unwrap();
+            std::os::unix::fs::symlink("d0", dir.join("across")).unwrap();
+        }
+    }
+
+    /// Everything about the entries of a walk that is shown.
+    fn listing(walker: &TreeWalker) -> Vec<String> {
+        walker
+            .walk()
+            .map(|entry| match entry {
+                Ok(entry) => format!(
+                    "{} {} {:?} {} {:?} {:?} {:?}",
+                    entry.index,
+                    entry.path.display(),
+                    entry.parent,
+                    entry.is_last,
+                    entry.kind,
+                    entry.elided,
+                    entry.error.map(|err| err.reason()),
+                ),
+                Err(err) => err.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn threads_keep_the_sequential_order() {
+        let dir = std::env::temp_dir().join(format!("treewalker-order-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        generate(&dir, 4, 4);
+
+        let configs: [fn(TreeWalker) -> TreeWalker; 4] = [
+            |walker| walker,
+            |walker| walker.include("*.md").unwrap(),
+            |walker| walker.follow_links(true).max_depth(6),
+            |walker| {
+                walker
+                    .follow_links(true)
+                    .include("*.md")
+                    .unwrap()
+                    .max_depth(6)
+            },
+        ];
+        for config in configs {
+            let expected = listing(&config(TreeWalker::new(&dir).threads(1)));
+            assert!(expected.len() > 100);
+            for threads in [2, 8] {
+                for _ in 0..3 {
+                    let walker = config(TreeWalker::new(&dir).threads(threads));
+                    assert_eq!(listing(&walker), expected, "{} threads", threads);
+                }
+            }
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn panic_in_callback_reaches_the_walk() {
+        let walker = TreeWalker::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src"))
+            .threads(4)
+            .sort_by(|a, b| {
+                if a.parent().is_some_and(|dir| dir.ends_with("render")) {
+                    panic!("sort failed");
+                }
+                a.cmp(b)
+            });
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| walker.walk().count()));
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"sort failed"));
+    }
+}
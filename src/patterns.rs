@@ -0,0 +1,50 @@
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// A set of glob patterns matched against paths relative to the walk root.
+///
+/// A pattern without a `/` matches a name at any depth, so `*.rs` behaves
+/// like `**/*.rs`. A leading `/` anchors the pattern at the root.
+#[derive(Clone)]
+pub(crate) struct Patterns {
+    globs: Vec<Glob>,
+    set: GlobSet,
+}
+
+impl Patterns {
+    pub(crate) fn new() -> Patterns {
+        Patterns {
+            globs: vec![],
+            set: GlobSet::empty(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, pattern: &str) -> Result<(), globset::Error> {
+        let pattern = if let Some(anchored) = pattern.strip_prefix('/') {
+            anchored.to_string()
+        } else if pattern.contains('/') {
+            pattern.to_string()
+        } else {
+            format!("**/{}", pattern)
+        };
+
+        let glob = GlobBuilder::new(&pattern).literal_separator(true).build()?;
+        self.globs.push(glob);
+
+        let mut builder = GlobSetBuilder::new();
+        for glob in &self.globs {
+            builder.add(glob.clone());
+        }
+        self.set = builder.build()?;
+
+        Ok(())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    pub(crate) fn is_match(&self, path: &Path) -> bool {
+        self.set.is_match(path)
+    }
+}
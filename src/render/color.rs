@@ -0,0 +1,186 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::walker::{is_executable, Entry, EntryKind};
+
+/// Used when `LS_COLORS` is unset, following GNU dircolors.
+const DEFAULT_LS_COLORS: &str = "di=01;34:ln=01;36:or=40;31;01:ex=01;32:\
+    *.tar=01;31:*.tgz=01;31:*.gz=01;31:*.bz2=01;31:*.xz=01;31:*.zst=01;31:\
+    *.zip=01;31:*.7z=01;31:*.rar=01;31:*.jar=01;31:*.deb=01;31:*.rpm=01;31:\
+    *.jpg=01;35:*.jpeg=01;35:*.png=01;35:*.gif=01;35:*.svg=01;35:*.webp=01;35:\
+    *.mp3=00;36:*.flac=00;36:*.ogg=00;36:*.wav=00;36";
+
+/// Name colors in the format of the `LS_COLORS` environment variable.
+#[derive(Debug, Clone)]
+pub struct LsColors {
+    dir: Option<String>,
+    file: Option<String>,
+    link: Option<String>,
+    /// `ln=target`: color links as the file they point to.
+    link_target: bool,
+    orphan: Option<String>,
+    exec: Option<String>,
+    /// `*.ext` style suffixes, lowercased.
+    suffixes: Vec<(String, String)>,
+}
+
+impl LsColors {
+    /// Colors from `LS_COLORS`, or the built-in defaults when it is unset.
+    pub fn from_env() -> LsColors {
+        match env::var("LS_COLORS") {
+            Ok(spec) if !spec.is_empty() => LsColors::parse(&spec),
+            _ => LsColors::parse(DEFAULT_LS_COLORS),
+        }
+    }
+
+    /// Parse a `key=SGR:key=SGR` list. Unknown keys, and codes that are not
+    /// digits and `;`, are ignored.
+    pub fn parse(spec: &str) -> LsColors {
+        let mut colors = LsColors {
+            dir: Some("01;34".to_string()),
+            file: None,
+            link: Some("01;36".to_string()),
+            link_target: false,
+            orphan: None,
+            exec: Some("01;32".to_string()),
+            suffixes: vec![],
+        };
+
+        for item in spec.split(':') {
+            let Some((key, code)) = item.split_once('=') else {
+                continue;
+            };
+            if key == "ln" && code == "target" {
+                colors.link_target = true;
+                continue;
+            }
+            if !code
+                .bytes()
+                .all(|byte| byte.is_ascii_digit() || byte == b';')
+            {
+                continue;
+            }
+            let code = match code.trim_start_matches('0') {
+                "" => None,
+                _ => Some(code.to_string()),
+            };
+
+            match key {
+                "di" => colors.dir = code,
+                "fi" => colors.file = code,
+                "ln" => {
+                    colors.link = code;
+                    colors.link_target = false;
+                }
+                "or" => colors.orphan = code,
+                "ex" => colors.exec = code,
+                _ => {
+                    if let (Some(suffix), Some(code)) = (key.strip_prefix('*'), code) {
+                        colors.suffixes.push((suffix.to_lowercase(), code));
+                    }
+                }
+            }
+        }
+
+        colors
+    }
+
+    /// The SGR sequence for an entry's name, if it has one.
+    pub fn style(&self, entry: &Entry) -> Option<&str> {
+        let code = match entry.kind {
+            EntryKind::Symlink => match &entry.link {
+                Some(link) if link.broken => self.orphan.as_ref().or(self.link.as_ref()),
+                Some(link) if self.link_target => match fs::metadata(&entry.path) {
+                    Ok(meta) if meta.is_dir() => self.dir.as_ref(),
+                    Ok(meta) => self.file_style(&link.target, || is_executable(&meta)),
+                    Err(_) => self.orphan.as_ref(),
+                },
+                _ => self.link.as_ref(),
+            },
+            EntryKind::Dir => self.dir.as_ref(),
+            EntryKind::File => self.file_style(&entry.path, || {
+                entry.executable.unwrap_or_else(|| {
+                    fs::metadata(&entry.path).is_ok_and(|meta| is_executable(&meta))
+                })
+            }),
+        };
+        code.map(String::as_str)
+    }
+
+    /// The color of a regular file named by `path`, checking whether it is
+    /// executable only when no suffix matches.
+    fn file_style(&self, path: &Path, executable: impl FnOnce() -> bool) -> Option<&String> {
+        self.suffix(path)
+            .or_else(|| self.exec.as_ref().filter(|_| executable()))
+            .or(self.file.as_ref())
+    }
+
+    /// The longest `*.ext` style suffix matching the name.
+    fn suffix(&self, path: &Path) -> Option<&String> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        self.suffixes
+            .iter()
+            .filter(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .max_by_key(|(suffix, _)| suffix.len())
+            .map(|(_, code)| code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_other_than_digits_are_ignored() {
+        let colors = LsColors::parse("di=target:fi=1;3x:ex=00:*.rs=38;5;208");
+        assert_eq!(colors.dir.as_deref(), Some("01;34"));
+        assert_eq!(colors.file, None);
+        assert_eq!(colors.exec, None);
+        assert_eq!(
+            colors.suffixes,
+            [(".rs".to_string(), "38;5;208".to_string())]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn link_target_colors_links_as_what_they_point_to() {
+        use crate::TreeWalker;
+
+        let dir = env::temp_dir().join(format!("treewalker-colors-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.tar"), "").unwrap();
+        std::os::unix::fs::symlink("sub", dir.join("to-dir")).unwrap();
+        std::os::unix::fs::symlink("a.tar", dir.join("to-tar")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.join("broken")).unwrap();
+
+        let colors = LsColors::parse("di=34:ln=target:or=31:*.tar=35");
+        let styles: Vec<_> = TreeWalker::new(&dir)
+            .walk()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let name = entry
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned();
+                (name, colors.style(&entry).map(str::to_string))
+            })
+            .skip(1)
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let style = |name: &str| {
+            styles
+                .iter()
+                .find(|(entry, _)| entry == name)
+                .and_then(|(_, style)| style.as_deref())
+        };
+        assert_eq!(style("to-dir"), Some("34"));
+        assert_eq!(style("to-tar"), Some("35"));
+        assert_eq!(style("broken"), Some("31"));
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::quote::{quote, QuotingStyle};
+use super::{display_name, relative_path};
+use crate::error::FileTreeError;
+use crate::walker::Entry;
+
+/// The diagram language a tree is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// A Graphviz `digraph`.
+    #[default]
+    Dot,
+    /// A Mermaid `flowchart`.
+    Mermaid,
+}
+
+/// Settings for [`print_graph`].
+#[derive(Debug, Clone, Default)]
+pub struct GraphOptions {
+    pub format: GraphFormat,
+    /// Leave out everything but directories.
+    pub dirs_only: bool,
+}
+
+/// Start a diagram, before the first tree.
+pub fn print_graph_start<W: Write>(
+    out: &mut W,
+    options: &GraphOptions,
+) -> Result<(), FileTreeError> {
+    match options.format {
+        GraphFormat::Dot => {
+            writeln!(out, "digraph tree {{")?;
+            writeln!(out, "    rankdir=LR;")?;
+            writeln!(out, "    node [shape=note, fontname=\"monospace\"];")?;
+        }
+        GraphFormat::Mermaid => {
+            writeln!(out, "flowchart LR")?;
+            writeln!(out, "    classDef dir font-weight:bold")?;
+            writeln!(out, "    classDef link stroke-dasharray:4")?;
+            writeln!(out, "    classDef error stroke:#cf222e")?;
+        }
+    }
+    Ok(())
+}
+
+/// Finish a diagram.
+pub fn print_graph_end<W: Write>(out: &mut W, options: &GraphOptions) -> Result<(), FileTreeError> {
+    if options.format == GraphFormat::Dot {
+        writeln!(out, "}}")?;
+    }
+    Ok(())
+}
+
+/// Add one tree to a diagram, as a node for each entry and an edge from
+/// each directory to its children.
+///
+/// Node IDs are the entries' paths relative to the root, so they stay the
+/// same wherever the tree is walked from, and only change when a file is
+/// renamed. `scope` is put in front of every ID to keep the trees of several
+/// roots in one diagram apart; it is empty for a single root.
+pub fn print_graph<W, I>(
+    out: &mut W,
+    entries: I,
+    options: &GraphOptions,
+    scope: &str,
+) -> Result<(), FileTreeError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<Entry, FileTreeError>>,
+{
+    let mut root = PathBuf::new();
+    // IDs of the directories seen so far, by entry index.
+    let mut dirs: HashMap<usize, String> = HashMap::new();
+
+    for entry in entries {
+        let entry = entry?;
+        if entry.depth == 0 {
+            root = entry.path.clone();
+        }
+        if options.dirs_only && !entry.is_dir() {
+            continue;
+        }
+
+        let mut key = OsString::from(scope);
+        key.push(relative_path(&entry.path, &root));
+        let id = match options.format {
+            GraphFormat::Dot => dot_id(&key),
+            GraphFormat::Mermaid => mermaid_id(&key),
+        };
+
+        let mut label = display_name(&entry.path).to_os_string();
+        if entry.is_dir() {
+            label.push("/");
+        }
+
+        match options.format {
+            GraphFormat::Dot => {
+                let mut attrs = format!("label={}", dot_id(&label));
+                if entry.is_dir() {
+                    attrs.push_str(", shape=folder");
+                }
+                if entry.link.is_some() {
+                    attrs.push_str(", style=dashed");
+                }
+                if entry.error.is_some() {
+                    attrs.push_str(", color=red");
+                }
+                writeln!(out, "    {} [{}];", id, attrs)?;
+            }
+            GraphFormat::Mermaid => {
+                let class = if entry.error.is_some() {
+                    ":::error"
+                } else if entry.is_dir() {
+                    ":::dir"
+                } else if entry.link.is_some() {
+                    ":::link"
+                } else {
+                    ""
+                };
+                writeln!(
+                    out,
+                    "    {}[\"{}\"]{}",
+                    id,
+                    mermaid_escape(&label.to_string_lossy()),
+                    class
+                )?;
+            }
+        }
+
+        if let Some(parent) = entry.parent.and_then(|index| dirs.get(&index)) {
+            match options.format {
+                GraphFormat::Dot => writeln!(out, "    {} -> {};", parent, id)?,
+                GraphFormat::Mermaid => writeln!(out, "    {} --> {}", parent, id)?,
+            }
+        }
+
+        if entry.is_dir() {
+            dirs.insert(entry.index, id);
+        }
+    }
+
+    Ok(())
+}
+
+/// A quoted DOT ID. The C quoting style escapes quotes, backslashes and
+/// anything unprintable, and keeps distinct names distinct.
+fn dot_id(key: &OsStr) -> String {
+    String::from_utf8_lossy(&quote(key, QuotingStyle::C)).into_owned()
+}
+
+/// A Mermaid node ID, which may only hold letters, digits and underscores.
+/// Every other byte becomes `_` and its hex value, and the leading `n`
+/// keeps IDs clear of keywords such as `end`.
+fn mermaid_id(key: &OsStr) -> String {
+    let mut id = String::from("n");
+    for &byte in key.as_encoded_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => id.push(byte as char),
+            _ => id.push_str(&format!("_{:02X}", byte)),
+        }
+    }
+    id
+}
+
+/// Escape a Mermaid label, which may contain markup and entity codes.
+fn mermaid_escape(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        match c {
+            '"' => escaped.push_str("#quot;"),
+            '#' => escaped.push_str("#35;"),
+            '&' => escaped.push_str("#amp;"),
+            '<' => escaped.push_str("#lt;"),
+            '>' => escaped.push_str("#gt;"),
+            c if c.is_control() => escaped.push('\u{FFFD}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,186 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::text::report;
+use super::{display_name, elided_marker, escape_markup, href, link_root};
+use crate::error::FileTreeError;
+use crate::size::SizeFormat;
+use crate::summary::Summary;
+use crate::walker::Entry;
+
+/// Styles for the page, kept inline so the file works on its own.
+const STYLE: &str = "\
+body { font: 14px/1.5 ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; margin: 2em; color: #24292f; background: #fff; }
+ul.tree, ul.tree ul { list-style: none; margin: 0; padding-left: 1.5em; }
+ul.tree { padding-left: 0; }
+summary { cursor: pointer; }
+summary::marker { color: #8c959f; }
+li.file { padding-left: 1.1em; }
+a { color: #0969da; text-decoration: none; }
+a:hover { text-decoration: underline; }
+.dir { font-weight: 600; }
+.size, .target, .elided, .report { color: #57606a; }
+.size { margin-left: 1em; }
+.broken, .error { color: #cf222e; }
+@media (prefers-color-scheme: dark) {
+  body { color: #c9d1d9; background: #0d1117; }
+  a { color: #58a6ff; }
+  .size, .target, .elided, .report { color: #8b949e; }
+  .broken, .error { color: #ff7b72; }
+}
+";
+
+/// Settings for [`print_html`].
+#[derive(Debug, Clone, Default)]
+pub struct HtmlOptions {
+    /// Show each entry's size after its name.
+    pub size: Option<SizeFormat>,
+    /// Link each name to its path.
+    pub links: bool,
+    /// The directory links are relative to, normally where the page will be
+    /// saved. Without one, links are relative to the current directory.
+    pub link_base: Option<PathBuf>,
+}
+
+/// Start a page, up to the point where trees are printed.
+pub fn print_html_start<W: Write>(out: &mut W, title: &str) -> Result<(), FileTreeError> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html lang=\"en\">")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(
+        out,
+        "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">"
+    )?;
+    writeln!(out, "<title>{}</title>", escape_markup(title, false))?;
+    writeln!(out, "<style>\n{}</style>", STYLE)?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    Ok(())
+}
+
+/// Finish a page, with the counts if given.
+pub fn print_html_end<W: Write>(
+    out: &mut W,
+    summary: Option<&Summary>,
+    options: &HtmlOptions,
+) -> Result<(), FileTreeError> {
+    if let Some(summary) = summary {
+        writeln!(
+            out,
+            "<p class=\"report\">{}</p>",
+            report(summary, options.size)
+        )?;
+    }
+
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")?;
+    Ok(())
+}
+
+/// Print one tree as nested lists, with a collapsible `<details>` element
+/// for each directory, and return the counts for [`print_html_end`].
+pub fn print_html<W, I>(
+    out: &mut W,
+    entries: I,
+    options: &HtmlOptions,
+) -> Result<Summary, FileTreeError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<Entry, FileTreeError>>,
+{
+    let mut summary = Summary::default();
+    let mut root = PathBuf::new();
+    let mut links_from = PathBuf::new();
+    let mut started = false;
+    // Depths of the directories whose lists are still open.
+    let mut open: Vec<usize> = vec![];
+
+    for entry in entries {
+        let entry = entry?;
+        summary.add(&entry);
+
+        // Opened here rather than up front so that a root that cannot be
+        // walked leaves nothing behind.
+        if entry.depth == 0 {
+            writeln!(out, "<ul class=\"tree\">")?;
+            started = true;
+            root = entry.path.clone();
+            if options.links {
+                links_from = link_root(&root, options.link_base.as_deref());
+            }
+        }
+
+        while open.last().is_some_and(|&depth| depth >= entry.depth) {
+            open.pop();
+            writeln!(out, "</ul></details></li>")?;
+        }
+
+        let name = display_name(&entry.path).to_string_lossy();
+        let mut label = escape_markup(&name, false);
+        if entry.is_dir() {
+            label.push('/');
+        }
+        if options.links {
+            label = format!(
+                "<a href=\"{}\">{}</a>",
+                href(&entry.path, &root, &links_from),
+                label
+            );
+        }
+
+        if let Some(link) = &entry.link {
+            label.push_str(&format!(
+                " <span class=\"target\">&rarr; {}</span>",
+                escape_markup(&link.target.to_string_lossy(), false)
+            ));
+            if link.broken {
+                label.push_str(" <span class=\"broken\">[broken]</span>");
+            }
+        }
+
+        if let (Some(format), Some(size)) = (options.size, entry.size) {
+            label.push_str(&format!(
+                "<span class=\"size\">{}</span>",
+                format.format(size)
+            ));
+        }
+
+        if let Some(err) = &entry.error {
+            label.push_str(&format!(
+                " <span class=\"error\">[error: {}]</span>",
+                escape_markup(&err.reason(), false)
+            ));
+        }
+
+        if entry.is_dir() {
+            // Only the root starts expanded.
+            let attr = if entry.depth == 0 { " open" } else { "" };
+            writeln!(
+                out,
+                "<li><details{}><summary class=\"dir\">{}</summary><ul>",
+                attr, label
+            )?;
+            open.push(entry.depth);
+
+            if let Some(count) = entry.elided {
+                writeln!(
+                    out,
+                    "<li class=\"elided\">{}</li>",
+                    elided_marker(count, "&hellip;")
+                )?;
+            }
+        } else {
+            writeln!(out, "<li class=\"file\">{}</li>", label)?;
+        }
+    }
+
+    for _ in &open {
+        writeln!(out, "</ul></details></li>")?;
+    }
+    if started {
+        writeln!(out, "</ul>")?;
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,97 @@
+use serde::Serialize;
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::{display_name, raw_bytes, relative_path};
+use crate::error::FileTreeError;
+use crate::tree::Node;
+use crate::walker::EntryKind;
+
+/// Nested JSON document for a tree.
+///
+/// Every node has `name`, `type` (`"directory"`, `"file"` or `"symlink"`) and
+/// `path` relative to the root (the root itself is `"."`). Names that are not
+/// valid UTF-8 are given lossily in these strings and exactly, as an array of
+/// bytes, in `name_bytes`, `path_bytes` and `target_bytes`. Symbolic links add
+/// their `target`, and `broken` when it does not exist. Directories also
+/// carry a `children` array in display order, and directories cut off by the
+/// depth limit an `elided` count of the children left out. With sizes
+/// enabled, `size` is in bytes and includes everything below a directory. A
+/// directory that could not be read has an `error` describing why.
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    name: Cow<'a, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_bytes: Option<&'a [u8]>,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    path: Cow<'a, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path_bytes: Option<&'a [u8]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_bytes: Option<&'a [u8]>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    broken: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<JsonNode<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elided: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl<'a> JsonNode<'a> {
+    fn new(node: &'a Node, root: &Path) -> JsonNode<'a> {
+        let children = match node.kind {
+            EntryKind::Dir => Some(
+                node.children
+                    .iter()
+                    .map(|child| JsonNode::new(child, root))
+                    .collect(),
+            ),
+            EntryKind::File | EntryKind::Symlink => None,
+        };
+
+        let name = display_name(&node.path);
+        let path = relative_path(&node.path, root);
+        let target = node.link.as_ref().map(|link| link.target.as_os_str());
+
+        JsonNode {
+            name: name.to_string_lossy(),
+            name_bytes: raw_bytes(name),
+            kind: node.kind.as_str(),
+            path: path.to_string_lossy(),
+            path_bytes: raw_bytes(path.as_os_str()),
+            target: target.map(|target| target.to_string_lossy()),
+            target_bytes: target.and_then(raw_bytes),
+            broken: node.link.as_ref().is_some_and(|link| link.broken),
+            children,
+            elided: node.elided,
+            size: node.size,
+            error: node.error.as_ref().map(|err| err.reason()),
+        }
+    }
+}
+
+pub fn print_json<W: Write>(out: &mut W, tree: &Node) -> Result<(), FileTreeError> {
+    let doc = JsonNode::new(tree, &tree.path);
+    serde_json::to_writer_pretty(&mut *out, &doc).map_err(io::Error::from)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Print several trees as a JSON array of documents, one per root.
+pub fn print_json_list<W: Write>(out: &mut W, trees: &[Node]) -> Result<(), FileTreeError> {
+    let docs: Vec<JsonNode> = trees
+        .iter()
+        .map(|tree| JsonNode::new(tree, &tree.path))
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &docs).map_err(io::Error::from)?;
+    writeln!(out)?;
+    Ok(())
+}
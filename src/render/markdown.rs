@@ -0,0 +1,168 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::text::{print_tree, TextOptions};
+use super::{display_name, elided_marker, href, link_root};
+use crate::error::FileTreeError;
+use crate::summary::Summary;
+use crate::walker::Entry;
+
+/// How a tree is written as Markdown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MarkdownStyle {
+    /// The text tree inside a fenced code block.
+    #[default]
+    Fence,
+    /// A nested bullet list with one item per entry.
+    List,
+}
+
+/// Settings for [`print_markdown`].
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownOptions {
+    pub style: MarkdownStyle,
+    /// In the list style, link each name to its path.
+    pub links: bool,
+    /// The directory links are relative to, normally where the document will
+    /// be saved. Without one, links are relative to the current directory.
+    pub link_base: Option<PathBuf>,
+    /// Drawing and size settings. Colors are never used.
+    pub text: TextOptions,
+}
+
+/// Print the tree as Markdown, and return the counts for
+/// [`super::text::print_report`].
+pub fn print_markdown<W, I>(
+    out: &mut W,
+    entries: I,
+    options: &MarkdownOptions,
+) -> Result<Summary, FileTreeError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<Entry, FileTreeError>>,
+{
+    match options.style {
+        MarkdownStyle::Fence => print_fence(out, entries, options),
+        MarkdownStyle::List => print_list(out, entries, options),
+    }
+}
+
+fn print_fence<W, I>(
+    out: &mut W,
+    entries: I,
+    options: &MarkdownOptions,
+) -> Result<Summary, FileTreeError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<Entry, FileTreeError>>,
+{
+    let text = TextOptions {
+        colors: None,
+        ..options.text.clone()
+    };
+
+    // The fence has to be longer than any run of backticks in a name, which
+    // is only known once the whole tree has been drawn.
+    let mut tree = vec![];
+    let summary = print_tree(&mut tree, entries, &text)?;
+
+    let longest = tree
+        .split(|&byte| byte != b'`')
+        .map(<[u8]>::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+
+    writeln!(out, "{}text", fence)?;
+    out.write_all(&tree)?;
+    writeln!(out, "{}", fence)?;
+
+    Ok(summary)
+}
+
+fn print_list<W, I>(
+    out: &mut W,
+    entries: I,
+    options: &MarkdownOptions,
+) -> Result<Summary, FileTreeError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<Entry, FileTreeError>>,
+{
+    let mut summary = Summary::default();
+    let mut root = PathBuf::new();
+    let mut links_from = PathBuf::new();
+
+    for entry in entries {
+        let entry = entry?;
+        summary.add(&entry);
+        if entry.depth == 0 && options.links {
+            root = entry.path.clone();
+            links_from = link_root(&root, options.link_base.as_deref());
+        }
+
+        let indent = "  ".repeat(entry.depth);
+        let mut name = escape(&display_name(&entry.path).to_string_lossy());
+        if entry.is_dir() {
+            name.push('/');
+        }
+
+        let mut item = if options.links {
+            format!("[{}]({})", name, href(&entry.path, &root, &links_from))
+        } else {
+            name
+        };
+
+        if let Some(link) = &entry.link {
+            item.push_str(&format!(" -> {}", escape(&link.target.to_string_lossy())));
+            if link.broken {
+                item.push_str(" (broken)");
+            }
+        }
+
+        if let (Some(format), Some(size)) = (options.text.size, entry.size) {
+            item.push_str(&format!(" ({})", format.format(size)));
+        }
+
+        if let Some(err) = &entry.error {
+            item.push_str(&format!(" *error: {}*", err.reason()));
+        }
+
+        writeln!(out, "{}- {}", indent, item)?;
+
+        if let Some(count) = entry.elided {
+            writeln!(
+                out,
+                "{}  - {}",
+                indent,
+                elided_marker(count, &options.text.theme.ellipsis)
+            )?;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Backslash-escape the characters that Markdown would otherwise read as
+/// formatting, and those that would start a heading or a nested list.
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    // The dot of a name like `1. Intro` would make an ordered list.
+    let ordinal = name
+        .find(|c: char| !c.is_ascii_digit())
+        .filter(|&end| end > 0 && name[end..].starts_with(". "));
+
+    for (i, c) in name.char_indices() {
+        let special = match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~' => true,
+            '#' | '+' | '-' => i == 0,
+            '.' => Some(i) == ordinal,
+            _ => false,
+        };
+        if special {
+            escaped.push('\\');
+        }
+        escaped.push(if c.is_control() { ' ' } else { c });
+    }
+    escaped
+}
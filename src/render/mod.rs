@@ -0,0 +1,219 @@
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+
+use crate::walker::relative_to;
+
+pub mod color;
+pub mod graph;
+pub mod html;
+pub mod json;
+pub mod markdown;
+pub mod ndjson;
+pub mod quote;
+pub mod text;
+pub mod theme;
+pub mod xml;
+
+/// The name shown for `path`, falling back to the whole path for roots such
+/// as `.` that have no final component.
+fn display_name(path: &Path) -> &OsStr {
+    path.file_name().unwrap_or(path.as_os_str())
+}
+
+/// `path` relative to `root`, with the root itself spelled `.`.
+fn relative_path<'a>(path: &'a Path, root: &Path) -> &'a Path {
+    match path.strip_prefix(root) {
+        Ok(rel) if rel.as_os_str().is_empty() => Path::new("."),
+        Ok(rel) => rel,
+        Err(_) => path,
+    }
+}
+
+/// The raw bytes of a name that is not valid UTF-8, for machine formats to
+/// carry alongside the lossy string.
+fn raw_bytes(name: &OsStr) -> Option<&[u8]> {
+    match name.to_str() {
+        Some(_) => None,
+        None => Some(name.as_encoded_bytes()),
+    }
+}
+
+/// How `root` is reached from `base`, the directory a generated document
+/// will be read from, which is the current directory if not given.
+fn link_root(root: &Path, base: Option<&Path>) -> PathBuf {
+    relative_to(&resolve(root), &resolve(base.unwrap_or(Path::new("."))))
+}
+
+/// `path` made absolute, with links resolved as far as it exists. The rest,
+/// such as an output directory yet to be created, is added as written, with
+/// `.` and `..` applied to the path so far.
+fn resolve(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut resolved = PathBuf::new();
+    let mut exists = true;
+
+    for part in path.components() {
+        match part {
+            Component::CurDir => continue,
+            Component::ParentDir if resolved.file_name().is_some() => {
+                resolved.pop();
+                continue;
+            }
+            Component::ParentDir if resolved.has_root() => continue,
+            _ => resolved.push(part),
+        }
+        if exists {
+            match resolved.canonicalize() {
+                Ok(real) => resolved = real,
+                Err(_) => exists = false,
+            }
+        }
+    }
+    resolved
+}
+
+/// A relative URL for `path`, an entry below `root`, with `root` spelled
+/// as `link_root`. Bytes outside the unreserved set are percent-encoded, so
+/// the result is safe in Markdown links and HTML attributes alike.
+fn href(path: &Path, root: &Path, link_root: &Path) -> String {
+    let path = match path.strip_prefix(root) {
+        Ok(rel) => link_root.join(rel),
+        Err(_) => path.to_path_buf(),
+    };
+
+    let mut href = String::new();
+    for part in path.components() {
+        match part {
+            Component::CurDir => continue,
+            Component::RootDir => {}
+            _ if !href.is_empty() && !href.ends_with('/') => href.push('/'),
+            _ => {}
+        }
+        for &byte in part.as_os_str().as_encoded_bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    href.push(byte as char)
+                }
+                _ => href.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+    }
+
+    if href.is_empty() {
+        href.push('.');
+    }
+    href
+}
+
+/// Escape text for HTML or XML element content and quoted attributes.
+/// Control characters, which neither allows, become U+FFFD, except that
+/// with `whitespace_refs` tabs and line breaks are written as character
+/// references so that attribute values keep them; without it tabs pass
+/// through as they are.
+fn escape_markup(text: &str, whitespace_refs: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            '\t' | '\n' | '\r' if whitespace_refs => escaped.push_str(&format!("&#{};", c as u32)),
+            '\t' => escaped.push(c),
+            c if c.is_control() => escaped.push('\u{FFFD}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Placeholder shown in place of the children of a directory at the depth
+/// limit.
+fn elided_marker(count: usize, ellipsis: &str) -> String {
+    if count == 1 {
+        format!("{} (1 entry)", ellipsis)
+    } else {
+        format!("{} ({} entries)", ellipsis, count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn href_respells_the_root() {
+        let root = Path::new("/work/repo");
+        let href =
+            |path: &str, link_root: &str| super::href(Path::new(path), root, Path::new(link_root));
+
+        assert_eq!(href("/work/repo/src/main.rs", "."), "src/main.rs");
+        assert_eq!(
+            href("/work/repo/src/main.rs", "../repo"),
+            "../repo/src/main.rs"
+        );
+        assert_eq!(href("/work/repo", "."), ".");
+        assert_eq!(href("/work/repo", "/abs/repo"), "/abs/repo");
+    }
+
+    #[test]
+    fn href_percent_encodes_reserved_bytes() {
+        let root = Path::new("r");
+        assert_eq!(
+            href(Path::new("r/a b/#1?&.md"), root, root),
+            "r/a%20b/%231%3F%26.md"
+        );
+        assert_eq!(href(Path::new("r/ü"), root, root), "r/%C3%BC");
+        assert_eq!(href(Path::new("r/x\"<y>"), root, root), "r/x%22%3Cy%3E");
+    }
+
+    #[test]
+    fn escape_markup_handles_whitespace_as_asked() {
+        assert_eq!(
+            escape_markup("a<b> & \"c\" 'd'", false),
+            "a&lt;b&gt; &amp; &quot;c&quot; &#39;d&#39;"
+        );
+        assert_eq!(escape_markup("a\tb\nc\x07", false), "a\tb\u{FFFD}c\u{FFFD}");
+        assert_eq!(escape_markup("a\tb\nc\x07", true), "a&#9;b&#10;c\u{FFFD}");
+    }
+
+    #[test]
+    fn link_root_climbs_from_the_base() {
+        let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let base = crate_dir.join("src").join("render");
+        assert_eq!(link_root(crate_dir, Some(&base)), Path::new("../.."));
+        assert_eq!(
+            link_root(&crate_dir.join("src"), Some(crate_dir)),
+            Path::new("src")
+        );
+    }
+
+    #[test]
+    fn link_root_to_a_base_yet_to_be_created() {
+        let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let base = crate_dir.join("target/no-such-dir/out");
+        assert_eq!(
+            link_root(&crate_dir.join("src"), Some(&base)),
+            Path::new("../../../src")
+        );
+        assert_eq!(
+            link_root(&crate_dir.join("src"), Some(&base.join("../../.."))),
+            Path::new("src")
+        );
+    }
+
+    #[test]
+    fn link_root_without_base_is_from_the_current_directory() {
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            link_root(Path::new("some/dir"), None),
+            Path::new("some/dir")
+        );
+        assert_eq!(
+            link_root(&cwd.join("some/dir"), None),
+            Path::new("some/dir")
+        );
+        assert_eq!(link_root(&cwd, None), Path::new("."));
+    }
+}
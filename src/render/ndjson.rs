@@ -0,0 +1,147 @@
+use serde::Serialize;
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use super::{display_name, raw_bytes, relative_path};
+use crate::error::FileTreeError;
+use crate::walker::Entry;
+
+/// One line of NDJSON output.
+///
+/// Records are written in walk order, so a reader can rebuild the tree by
+/// attaching each record to the one at `parent`. The root has index 0 and a
+/// null parent. Fields are as in the JSON format, including the `*_bytes`
+/// arrays for names that are not valid UTF-8, except that a directory has
+/// no `size`: its total follows in a [`Total`] once everything below it has
+/// been written.
+#[derive(Serialize)]
+struct Record<'a> {
+    index: usize,
+    parent: Option<usize>,
+    depth: usize,
+    name: Cow<'a, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_bytes: Option<&'a [u8]>,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    path: Cow<'a, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path_bytes: Option<&'a [u8]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_bytes: Option<&'a [u8]>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    broken: bool,
+    is_last: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elided: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The disk usage of a directory and everything below it, written after
+/// the directory's last descendant as `{"index": ..., "total": ...}`.
+#[derive(Serialize)]
+struct Total {
+    index: usize,
+    total: u64,
+}
+
+/// A directory whose total is still being added up.
+struct Open {
+    index: usize,
+    depth: usize,
+    total: u64,
+    counted: bool,
+}
+
+/// Print entries as they are walked, one record per line. With sizes, the
+/// entries must come from [`crate::TreeWalker::walk`], whose directories
+/// carry their own size, for the totals to be added up here.
+pub fn print_ndjson<W, I>(out: &mut W, entries: I) -> Result<(), FileTreeError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<Entry, FileTreeError>>,
+{
+    let mut root = PathBuf::new();
+    let mut open: Vec<Open> = vec![];
+
+    for entry in entries {
+        let entry = entry?;
+        if entry.depth == 0 {
+            root = entry.path.clone();
+        }
+
+        while open.last().is_some_and(|dir| dir.depth >= entry.depth) {
+            close(out, &mut open)?;
+        }
+        if let (Some(size), true) = (entry.size, entry.counted) {
+            if let (Some(parent), false) = (open.last_mut(), entry.is_dir()) {
+                parent.total += size;
+            }
+        }
+
+        let name = display_name(&entry.path);
+        let path = relative_path(&entry.path, &root);
+        let target = entry.link.as_ref().map(|link| link.target.as_os_str());
+
+        let record = Record {
+            index: entry.index,
+            parent: entry.parent,
+            depth: entry.depth,
+            name: name.to_string_lossy(),
+            name_bytes: raw_bytes(name),
+            kind: entry.kind.as_str(),
+            path: path.to_string_lossy(),
+            path_bytes: raw_bytes(path.as_os_str()),
+            target: target.map(|target| target.to_string_lossy()),
+            target_bytes: target.and_then(raw_bytes),
+            broken: entry.link.as_ref().is_some_and(|link| link.broken),
+            is_last: entry.is_last,
+            elided: entry.elided,
+            size: entry.size.filter(|_| !entry.is_dir()),
+            error: entry.error.as_ref().map(|err| err.reason()),
+        };
+
+        serde_json::to_writer(&mut *out, &record).map_err(io::Error::from)?;
+        writeln!(out)?;
+
+        if let (Some(size), true) = (entry.size, entry.is_dir()) {
+            open.push(Open {
+                index: entry.index,
+                depth: entry.depth,
+                total: size,
+                counted: entry.counted,
+            });
+        }
+    }
+
+    while !open.is_empty() {
+        close(out, &mut open)?;
+    }
+
+    Ok(())
+}
+
+/// Write the total of the innermost open directory and add it to its
+/// parent's.
+fn close<W: Write>(out: &mut W, open: &mut Vec<Open>) -> Result<(), FileTreeError> {
+    let Some(dir) = open.pop() else {
+        return Ok(());
+    };
+    if let (Some(parent), true) = (open.last_mut(), dir.counted) {
+        parent.total += dir.total;
+    }
+
+    let total = Total {
+        index: dir.index,
+        total: dir.total,
+    };
+    serde_json::to_writer(&mut *out, &total).map_err(io::Error::from)?;
+    writeln!(out)?;
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::borrow::Cow;
+use std::ffi::OsStr;
+
+/// How names are written in text output, following GNU `ls`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuotingStyle {
+    /// The raw bytes of the name, unchanged.
+    #[default]
+    Literal,
+    /// Backslash escapes for control characters, backslashes and bytes that
+    /// are not valid UTF-8.
+    Escape,
+    /// Single-quoted where needed so the name can be pasted into a shell,
+    /// with `$'...'` for characters that cannot appear literally.
+    Shell,
+    /// Always double-quoted, with escapes as in a C string literal.
+    C,
+}
+
+/// `name` as bytes ready to be written in the given style.
+pub fn quote(name: &OsStr, style: QuotingStyle) -> Cow<'_, [u8]> {
+    let bytes = name.as_encoded_bytes();
+
+    match style {
+        QuotingStyle::Literal => Cow::Borrowed(bytes),
+        QuotingStyle::Escape => {
+            let mut out = vec![];
+            escape(&mut out, bytes, None);
+            Cow::Owned(out)
+        }
+        QuotingStyle::C => {
+            let mut out = vec![b'"'];
+            escape(&mut out, bytes, Some('"'));
+            out.push(b'"');
+            Cow::Owned(out)
+        }
+        QuotingStyle::Shell if is_shell_safe(bytes) => Cow::Borrowed(bytes),
+        QuotingStyle::Shell => Cow::Owned(shell(bytes)),
+    }
+}
+
+/// Append `bytes` with backslash escapes, also escaping `quote` if given.
+fn escape(out: &mut Vec<u8>, bytes: &[u8], quote: Option<char>) {
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c == '\\' || Some(c) == quote {
+                out.push(b'\\');
+                push_char(out, c);
+            } else if c.is_control() {
+                escape_char(out, c);
+            } else {
+                push_char(out, c);
+            }
+        }
+        for &byte in chunk.invalid() {
+            escape_byte(out, byte);
+        }
+    }
+}
+
+/// Whether a name can be used as a shell word without quoting.
+fn is_shell_safe(bytes: &[u8]) -> bool {
+    let Ok(name) = std::str::from_utf8(bytes) else {
+        return false;
+    };
+    !name.is_empty()
+        && !name.starts_with(['~', '-'])
+        && name.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || "._-+,:@%/=".contains(c)
+                || (!c.is_ascii() && !c.is_control())
+        })
+}
+
+/// Quote `bytes` for a POSIX shell. Printable runs go in single quotes, and
+/// everything else in `$'...'` escapes between them.
+fn shell(bytes: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut open = false;
+
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c.is_control() {
+                if open {
+                    out.push(b'\'');
+                    open = false;
+                }
+                out.extend_from_slice(b"$'");
+                escape_char(&mut out, c);
+                out.push(b'\'');
+            } else {
+                if !open {
+                    out.push(b'\'');
+                    open = true;
+                }
+                if c == '\'' {
+                    out.extend_from_slice(b"'\\''");
+                } else {
+                    push_char(&mut out, c);
+                }
+            }
+        }
+
+        if !chunk.invalid().is_empty() {
+            if open {
+                out.push(b'\'');
+                open = false;
+            }
+            out.extend_from_slice(b"$'");
+            for &byte in chunk.invalid() {
+                escape_byte(&mut out, byte);
+            }
+            out.push(b'\'');
+        }
+    }
+
+    if open {
+        out.push(b'\'');
+    }
+    out
+}
+
+fn push_char(out: &mut Vec<u8>, c: char) {
+    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+/// The C escape for a control character, falling back to octal for each of
+/// its bytes.
+fn escape_char(out: &mut Vec<u8>, c: char) {
+    let short = match c {
+        '\x07' => "\\a",
+        '\x08' => "\\b",
+        '\t' => "\\t",
+        '\n' => "\\n",
+        '\x0b' => "\\v",
+        '\x0c' => "\\f",
+        '\r' => "\\r",
+        _ => {
+            for &byte in c.encode_utf8(&mut [0; 4]).as_bytes() {
+                escape_byte(out, byte);
+            }
+            return;
+        }
+    };
+    out.extend_from_slice(short.as_bytes());
+}
+
+fn escape_byte(out: &mut Vec<u8>, byte: u8) {
+    out.extend_from_slice(format!("\\{:03o}", byte).as_bytes());
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+
+    fn quoted(name: &[u8], style: QuotingStyle) -> String {
+        let name = OsStr::from_bytes(name);
+        String::from_utf8(quote(name, style).into_owned()).unwrap()
+    }
+
+    #[test]
+    fn shell_leaves_safe_names_bare() {
+        assert_eq!(quoted(b"src/main.rs", QuotingStyle::Shell), "src/main.rs");
+    }
+
+    #[test]
+    fn shell_quotes_single_quotes() {
+        assert_eq!(quoted(b"it's", QuotingStyle::Shell), r"'it'\''s'");
+        assert_eq!(quoted(b"a b", QuotingStyle::Shell), "'a b'");
+        assert_eq!(quoted(b"-n", QuotingStyle::Shell), "'-n'");
+    }
+
+    #[test]
+    fn shell_escapes_control_characters_and_invalid_bytes() {
+        assert_eq!(quoted(b"a\nb", QuotingStyle::Shell), r"'a'$'\n''b'");
+        assert_eq!(quoted(b"x\xffy", QuotingStyle::Shell), r"'x'$'\377''y'");
+        assert_eq!(quoted(b"\xfe\xff", QuotingStyle::Shell), r"$'\376'$'\377'");
+    }
+
+    #[test]
+    fn escape_and_c_styles() {
+        assert_eq!(quoted(b"a\\b\t", QuotingStyle::Escape), r"a\\b\t");
+        assert_eq!(quoted(b"say \"hi\"", QuotingStyle::C), r#""say \"hi\"""#);
+        assert_eq!(quoted(b"\xff", QuotingStyle::C), r#""\377""#);
+    }
+}
@@ -0,0 +1,156 @@
+use std::ffi::OsStr;
+use std::io::Write;
+
+use super::color::LsColors;
+use super::quote::{quote, QuotingStyle};
+use super::theme::Theme;
+use super::{display_name, elided_marker};
+use crate::error::FileTreeError;
+use crate::size::SizeFormat;
+use crate::summary::Summary;
+use crate::walker::Entry;
+
+/// Settings for [`print_tree`].
+#[derive(Debug, Clone, Default)]
+pub struct TextOptions {
+    /// Show each entry's size before its name.
+    pub size: Option<SizeFormat>,
+    /// Color names. The branch prefixes are never colored, so the layout
+    /// lines up the same with and without color.
+    pub colors: Option<LsColors>,
+    /// How names and link targets are escaped.
+    pub quoting: QuotingStyle,
+    /// The characters branches are drawn with.
+    pub theme: Theme,
+}
+
+/// Print the tree starting with a line for the root, and return the counts
+/// for [`print_report`].
+pub fn print_tree<W, I>(
+    out: &mut W,
+    entries: I,
+    options: &TextOptions,
+) -> Result<Summary, FileTreeError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<Entry, FileTreeError>>,
+{
+    // Whether each ancestor below the root was the last of its siblings.
+    let mut ancestors: Vec<bool> = vec![];
+    let mut summary = Summary::default();
+
+    for entry in entries {
+        let entry = entry?;
+        summary.add(&entry);
+
+        if entry.depth == 0 {
+            out.write_all(&decorate(&entry, entry.path.as_os_str(), false, options))?;
+            writeln!(out)?;
+        } else {
+            ancestors.truncate(entry.depth - 1);
+
+            let mut prefix = indent(&ancestors, &options.theme);
+            prefix.push_str(&options.theme.branch(entry.is_last));
+
+            if let (Some(format), Some(size)) = (options.size, entry.size) {
+                prefix.push_str(&format!("[{:>10}]  ", format.format(size)));
+            }
+
+            let line = decorate(&entry, display_name(&entry.path), entry.is_dir(), options);
+            out.write_all(prefix.as_bytes())?;
+            out.write_all(&line)?;
+            writeln!(out)?;
+
+            ancestors.push(entry.is_last);
+        }
+
+        if let Some(count) = entry.elided {
+            writeln!(
+                out,
+                "{}{}{}",
+                indent(&ancestors, &options.theme),
+                options.theme.branch(true),
+                elided_marker(count, &options.theme.ellipsis)
+            )?;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Print the `N directories, M files` footer, preceded by a blank line.
+pub fn print_report<W: Write>(
+    out: &mut W,
+    summary: &Summary,
+    options: &TextOptions,
+) -> Result<(), FileTreeError> {
+    writeln!(out)?;
+    writeln!(out, "{}", report(summary, options.size))?;
+    Ok(())
+}
+
+/// The counts as a line of text, led by the total size if there is one.
+pub(crate) fn report(summary: &Summary, size: Option<SizeFormat>) -> String {
+    let dirs = match summary.dirs {
+        1 => "1 directory".to_string(),
+        n => format!("{} directories", n),
+    };
+    let files = match summary.files {
+        1 => "1 file".to_string(),
+        n => format!("{} files", n),
+    };
+
+    match (size, summary.size) {
+        (Some(format), Some(size)) => {
+            format!("{} used in {}, {}", format.format(size), dirs, files)
+        }
+        _ => format!("{}, {}", dirs, files),
+    }
+}
+
+/// The name of an entry with its color, directory slash, link target and
+/// any error.
+///
+/// Names are bytes rather than strings so that the literal quoting style can
+/// pass through names that are not valid UTF-8 unchanged.
+fn decorate(entry: &Entry, name: &OsStr, slash: bool, options: &TextOptions) -> Vec<u8> {
+    let name = quote(name, options.quoting);
+    let mut line = vec![];
+    match options
+        .colors
+        .as_ref()
+        .and_then(|colors| colors.style(entry))
+    {
+        Some(code) => {
+            line.extend_from_slice(format!("\x1b[{}m", code).as_bytes());
+            line.extend_from_slice(&name);
+            line.extend_from_slice(b"\x1b[0m");
+        }
+        None => line.extend_from_slice(&name),
+    }
+    if slash {
+        line.push(b'/');
+    }
+
+    if let Some(link) = &entry.link {
+        line.extend_from_slice(b" -> ");
+        line.extend_from_slice(&quote(link.target.as_os_str(), options.quoting));
+        if link.broken {
+            line.extend_from_slice(b" [broken]");
+        }
+    }
+
+    if let Some(err) = &entry.error {
+        line.extend_from_slice(format!(" [error: {}]", err.reason()).as_bytes());
+    }
+
+    line
+}
+
+fn indent(ancestors: &[bool], theme: &Theme) -> String {
+    let mut indent = String::new();
+    for &is_last in ancestors {
+        indent.push_str(&theme.column(is_last));
+    }
+    indent
+}
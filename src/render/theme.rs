@@ -0,0 +1,251 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::FileTreeError;
+
+/// The widest a level of the tree may be drawn, in columns.
+pub const MAX_INDENT: u16 = 64;
+
+/// Built-in sets of branch-drawing characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// `|--`, `` `-- `` and `|`, for terminals and logs without Unicode.
+    Ascii,
+    /// Box-drawing lines, `├──`, `└──` and `│`.
+    Unicode,
+    /// Box-drawing lines with a rounded last branch, `╰──`.
+    Rounded,
+    /// Heavy box-drawing lines, `┣━━`, `┗━━` and `┃`.
+    Heavy,
+    /// Double box-drawing lines, `╠══`, `╚══` and `║`.
+    Double,
+}
+
+/// The characters the text tree is drawn with.
+///
+/// Each level is `indent` columns wide: a branch is `tee` or `corner`
+/// followed by `horizontal` up to the last column, which is a space, and
+/// the column under an ancestor with more siblings holds `vertical`. The
+/// characters are assumed to be one column wide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub tee: String,
+    pub corner: String,
+    pub vertical: String,
+    pub horizontal: String,
+    /// Stands in for the children of a directory at the depth limit.
+    pub ellipsis: String,
+    /// Columns per level, from 2 to [`MAX_INDENT`].
+    pub indent: usize,
+}
+
+impl Theme {
+    pub fn new(charset: Charset) -> Theme {
+        let (tee, corner, vertical, horizontal, ellipsis) = match charset {
+            Charset::Ascii => ("|", "`", "|", "-", "..."),
+            Charset::Unicode => ("├", "└", "│", "─", "…"),
+            Charset::Rounded => ("├", "╰", "│", "─", "…"),
+            Charset::Heavy => ("┣", "┗", "┃", "━", "…"),
+            Charset::Double => ("╠", "╚", "║", "═", "…"),
+        };
+
+        Theme {
+            tee: tee.to_string(),
+            corner: corner.to_string(),
+            vertical: vertical.to_string(),
+            horizontal: horizontal.to_string(),
+            ellipsis: ellipsis.to_string(),
+            indent: 4,
+        }
+    }
+
+    /// Read overrides for this theme from a file of `key = value` lines.
+    ///
+    /// The keys are `charset` (one of the built-in names, applied first),
+    /// `tee`, `corner`, `vertical`, `horizontal`, `ellipsis` and `indent`. Values may be
+    /// quoted, and lines starting with `#` are comments. The branch
+    /// characters must be a single character each.
+    pub fn load(self, path: &Path) -> Result<Theme, FileTreeError> {
+        let error = |message: String| FileTreeError::Config {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let text = fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+        let mut theme = self;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(format!(
+                    "line {}: expected `key = value`",
+                    number + 1
+                )));
+            };
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+
+            match key.trim() {
+                "charset" => match value.parse() {
+                    Ok(charset) => {
+                        let indent = theme.indent;
+                        theme = Theme {
+                            indent,
+                            ..Theme::new(charset)
+                        };
+                    }
+                    Err(()) => {
+                        return Err(error(format!(
+                            "line {}: unknown charset `{}`",
+                            number + 1,
+                            value
+                        )))
+                    }
+                },
+                key @ ("tee" | "corner" | "vertical" | "horizontal") => {
+                    if value.chars().count() != 1 {
+                        return Err(error(format!(
+                            "line {}: {} must be a single character",
+                            number + 1,
+                            key
+                        )));
+                    }
+                    let field = match key {
+                        "tee" => &mut theme.tee,
+                        "corner" => &mut theme.corner,
+                        "vertical" => &mut theme.vertical,
+                        _ => &mut theme.horizontal,
+                    };
+                    *field = value.to_string();
+                }
+                "ellipsis" => theme.ellipsis = value.to_string(),
+                "indent" => match value.parse::<u16>() {
+                    Ok(indent) if (2..=MAX_INDENT).contains(&indent) => {
+                        theme.indent = usize::from(indent)
+                    }
+                    _ => {
+                        return Err(error(format!(
+                            "line {}: indent must be a number from 2 to {}",
+                            number + 1,
+                            MAX_INDENT
+                        )))
+                    }
+                },
+                key => return Err(error(format!("line {}: unknown key `{}`", number + 1, key))),
+            }
+        }
+
+        Ok(theme)
+    }
+
+    /// The branch in front of an entry's name.
+    pub(crate) fn branch(&self, is_last: bool) -> String {
+        let start = if is_last { &self.corner } else { &self.tee };
+        format!(
+            "{}{} ",
+            start,
+            self.horizontal.repeat(self.indent.max(2) - 2)
+        )
+    }
+
+    /// The column under an ancestor, continuing its line if it has more
+    /// siblings to come.
+    pub(crate) fn column(&self, is_last: bool) -> String {
+        if is_last {
+            " ".repeat(self.indent.max(2))
+        } else {
+            format!("{}{}", self.vertical, " ".repeat(self.indent.max(2) - 1))
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::new(Charset::Unicode)
+    }
+}
+
+impl std::str::FromStr for Charset {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Charset, ()> {
+        match name {
+            "ascii" => Ok(Charset::Ascii),
+            "unicode" => Ok(Charset::Unicode),
+            "rounded" => Ok(Charset::Rounded),
+            "heavy" => Ok(Charset::Heavy),
+            "double" => Ok(Charset::Double),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Load `text` as a theme file over the default theme.
+    fn load(name: &str, text: &str) -> Result<Theme, FileTreeError> {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("treewalker-{}-{}.theme", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let theme = Theme::default().load(&path);
+        fs::remove_file(&path).unwrap();
+        theme
+    }
+
+    #[test]
+    fn overrides_apply_after_charset() {
+        let theme = load(
+            "overrides",
+            "# comment\nindent = 3\ncharset = ascii\ncorner = \"+\"\n",
+        )
+        .unwrap();
+        assert_eq!(theme.indent, 3);
+        assert_eq!(theme.tee, "|");
+        assert_eq!(theme.corner, "+");
+        assert_eq!(theme.branch(true), "+- ");
+    }
+
+    #[test]
+    fn invalid_indent_is_rejected_with_its_line() {
+        for value in ["1", "wide", "-4", "65", "18446744073709551615"] {
+            let err = load("indent", &format!("tee = |\nindent = {}\n", value)).unwrap_err();
+            assert!(matches!(err, FileTreeError::Config { .. }));
+            assert_eq!(err.reason(), "line 2: indent must be a number from 2 to 64");
+        }
+    }
+
+    #[test]
+    fn branch_characters_must_be_single() {
+        for line in ["tee = \"\"", "corner = ++", "vertical = \"||\""] {
+            let key = line.split_whitespace().next().unwrap();
+            let err = load("single", &format!("{}\n", line)).unwrap_err();
+            assert_eq!(
+                err.reason(),
+                format!("line 1: {} must be a single character", key)
+            );
+        }
+
+        let theme = load("wide-ellipsis", "horizontal = ═\nellipsis = [more]\n").unwrap();
+        assert_eq!(theme.horizontal, "═");
+        assert_eq!(theme.ellipsis, "[more]");
+    }
+
+    #[test]
+    fn unknown_keys_and_charsets_are_rejected() {
+        let err = load("key", "colour = red\n").unwrap_err();
+        assert_eq!(err.reason(), "line 1: unknown key `colour`");
+
+        let err = load("charset", "charset = fancy\n").unwrap_err();
+        assert_eq!(err.reason(), "line 1: unknown charset `fancy`");
+    }
+}
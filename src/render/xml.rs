@@ -0,0 +1,119 @@
+use std::io::Write;
+
+use super::{display_name, escape_markup};
+use crate::error::FileTreeError;
+use crate::summary::Summary;
+use crate::walker::{Entry, EntryKind};
+
+/// Settings for [`print_xml`].
+#[derive(Debug, Clone, Default)]
+pub struct XmlOptions {
+    /// Give each entry a `size` attribute, in bytes.
+    pub size: bool,
+}
+
+/// Start a document, before the first tree.
+pub fn print_xml_start<W: Write>(out: &mut W) -> Result<(), FileTreeError> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<tree>")?;
+    Ok(())
+}
+
+/// Finish a document, with a `<report>` of the counts if given.
+pub fn print_xml_end<W: Write>(
+    out: &mut W,
+    summary: Option<&Summary>,
+    options: &XmlOptions,
+) -> Result<(), FileTreeError> {
+    if let Some(summary) = summary {
+        writeln!(out, "  <report>")?;
+        if let (true, Some(size)) = (options.size, summary.size) {
+            writeln!(out, "    <size>{}</size>", size)?;
+        }
+        writeln!(out, "    <directories>{}</directories>", summary.dirs)?;
+        writeln!(out, "    <files>{}</files>", summary.files)?;
+        writeln!(out, "  </report>")?;
+    }
+    writeln!(out, "</tree>")?;
+    Ok(())
+}
+
+/// Print one tree as the XML of GNU `tree -X`: nested `<directory>`
+/// elements holding `<file>` and `<link>` elements, and an `<error>` in a
+/// directory that could not be read. Returns the counts for
+/// [`print_xml_end`].
+pub fn print_xml<W, I>(
+    out: &mut W,
+    entries: I,
+    options: &XmlOptions,
+) -> Result<Summary, FileTreeError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<Entry, FileTreeError>>,
+{
+    let mut summary = Summary::default();
+    // Depths and element names of the directories still open.
+    let mut open: Vec<(usize, &str)> = vec![];
+
+    for entry in entries {
+        let entry = entry?;
+        summary.add(&entry);
+
+        while let Some(&(depth, element)) = open.last() {
+            if depth < entry.depth {
+                break;
+            }
+            open.pop();
+            writeln!(out, "{}</{}>", indent(depth), element)?;
+        }
+
+        let element = match (&entry.link, entry.kind) {
+            (Some(_), _) => "link",
+            (None, EntryKind::Dir) => "directory",
+            (None, _) => "file",
+        };
+
+        // Like `tree`, the root is named by its whole path.
+        let name = match entry.depth {
+            0 => entry.path.as_os_str(),
+            _ => display_name(&entry.path),
+        };
+        let mut attrs = format!(" name=\"{}\"", escape_markup(&name.to_string_lossy(), true));
+        if let Some(link) = &entry.link {
+            attrs.push_str(&format!(
+                " target=\"{}\"",
+                escape_markup(&link.target.to_string_lossy(), true)
+            ));
+        }
+        if let (true, Some(size)) = (options.size, entry.size) {
+            attrs.push_str(&format!(" size=\"{}\"", size));
+        }
+
+        let pad = indent(entry.depth);
+        if entry.is_dir() {
+            writeln!(out, "{}<{}{}>", pad, element, attrs)?;
+            if let Some(err) = &entry.error {
+                writeln!(
+                    out,
+                    "{}  <error>{}</error>",
+                    pad,
+                    escape_markup(&err.reason(), true)
+                )?;
+            }
+            open.push((entry.depth, element));
+        } else {
+            writeln!(out, "{}<{}{}></{}>", pad, element, attrs, element)?;
+        }
+    }
+
+    while let Some((depth, element)) = open.pop() {
+        writeln!(out, "{}</{}>", indent(depth), element)?;
+    }
+
+    Ok(summary)
+}
+
+/// Leading spaces for an element at `depth`, inside `<tree>`.
+fn indent(depth: usize) -> String {
+    "  ".repeat(depth + 1)
+}
@@ -0,0 +1,36 @@
+/// How file sizes are written in text output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeFormat {
+    /// Exact byte counts.
+    Bytes,
+    /// Powers of 1024: KiB, MiB, GiB, ...
+    Iec,
+    /// Powers of 1000: kB, MB, GB, ...
+    Si,
+}
+
+impl SizeFormat {
+    pub fn format(&self, bytes: u64) -> String {
+        match self {
+            SizeFormat::Bytes => bytes.to_string(),
+            SizeFormat::Iec => scaled(bytes, 1024, &["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]),
+            SizeFormat::Si => scaled(bytes, 1000, &["kB", "MB", "GB", "TB", "PB", "EB"]),
+        }
+    }
+}
+
+fn scaled(bytes: u64, base: u64, units: &[&str]) -> String {
+    if bytes < base {
+        return format!("{} B", bytes);
+    }
+
+    let base = base as f64;
+    let mut value = bytes as f64 / base;
+    let mut unit = 0;
+    while value >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, units[unit])
+}
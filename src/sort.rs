@@ -0,0 +1,217 @@
+use std::cmp::Ordering;
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::walker::Child;
+
+/// What siblings are ordered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Byte-wise by name.
+    Name,
+    /// By name, comparing runs of digits by their numeric value, so that
+    /// `file2` sorts before `file10`.
+    Natural,
+    /// Largest first. Directories are compared by their own size, not the
+    /// totals shown with [`crate::TreeWalker::sizes`], which are only known
+    /// once everything below them has been walked.
+    Size,
+    /// Most recently modified first.
+    Mtime,
+    /// Most recently changed first (creation time where there is no ctime).
+    Ctime,
+    /// By extension, then by name.
+    Extension,
+    /// Directory order as returned by the operating system.
+    None,
+}
+
+/// Where directories go relative to files among siblings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirOrder {
+    First,
+    Last,
+    Mixed,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SortOrder {
+    pub(crate) key: SortKey,
+    pub(crate) dirs: DirOrder,
+    pub(crate) reverse: bool,
+    pub(crate) ignore_case: bool,
+}
+
+impl Default for SortOrder {
+    fn default() -> SortOrder {
+        SortOrder {
+            key: SortKey::Name,
+            dirs: DirOrder::First,
+            reverse: false,
+            ignore_case: false,
+        }
+    }
+}
+
+impl SortOrder {
+    /// Sort the children of a directory. Links to directories group with
+    /// directories only when they are followed, which is how the children
+    /// were classified. Metadata is only read for the size and time keys.
+    pub(crate) fn sort(&self, children: &mut [Child]) {
+        children.sort_by(|a, b| self.compare(a, b));
+    }
+
+    fn compare(&self, a: &Child, b: &Child) -> Ordering {
+        let group = match self.dirs {
+            DirOrder::First => b.is_dir().cmp(&a.is_dir()),
+            DirOrder::Last => a.is_dir().cmp(&b.is_dir()),
+            DirOrder::Mixed => Ordering::Equal,
+        };
+
+        let key = match self.key {
+            SortKey::Name => self.compare_names(&a.path, &b.path),
+            SortKey::Natural => natural_cmp(&self.name(&a.path), &self.name(&b.path))
+                .then_with(|| a.path.cmp(&b.path)),
+            SortKey::Size => size(b).cmp(&size(a)),
+            SortKey::Mtime => mtime(b).cmp(&mtime(a)),
+            SortKey::Ctime => ctime(b).cmp(&ctime(a)),
+            SortKey::Extension => self
+                .extension(&a.path)
+                .cmp(&self.extension(&b.path))
+                .then_with(|| self.compare_names(&a.path, &b.path)),
+            SortKey::None => Ordering::Equal,
+        };
+
+        // Ties on size and time fall back to the name, which is not reversed.
+        let key = if self.reverse { key.reverse() } else { key };
+        let key = match self.key {
+            SortKey::Size | SortKey::Mtime | SortKey::Ctime => {
+                key.then_with(|| self.compare_names(&a.path, &b.path))
+            }
+            _ => key,
+        };
+
+        group.then(key)
+    }
+
+    fn compare_names(&self, a: &Path, b: &Path) -> Ordering {
+        if self.ignore_case {
+            self.name(a).cmp(&self.name(b)).then_with(|| a.cmp(b))
+        } else {
+            a.cmp(b)
+        }
+    }
+
+    fn name(&self, path: &Path) -> String {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if self.ignore_case {
+            name.to_lowercase()
+        } else {
+            name.into_owned()
+        }
+    }
+
+    fn extension(&self, path: &Path) -> String {
+        let ext = path.extension().unwrap_or_default().to_string_lossy();
+        if self.ignore_case {
+            ext.to_lowercase()
+        } else {
+            ext.into_owned()
+        }
+    }
+}
+
+fn size(child: &Child) -> u64 {
+    child.metadata().map_or(0, Metadata::len)
+}
+
+fn mtime(child: &Child) -> SystemTime {
+    child
+        .metadata()
+        .and_then(|meta| meta.modified().ok())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+#[cfg(unix)]
+fn ctime(child: &Child) -> (i64, i64) {
+    use std::os::unix::fs::MetadataExt;
+
+    child
+        .metadata()
+        .map_or((0, 0), |meta| (meta.ctime(), meta.ctime_nsec()))
+}
+
+#[cfg(not(unix))]
+fn ctime(child: &Child) -> SystemTime {
+    child
+        .metadata()
+        .and_then(|meta| meta.created().ok())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Compare two names, treating each run of ASCII digits as a number.
+fn natural_cmp(mut a: &str, mut b: &str) -> Ordering {
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (a_num, a_rest) = split_digits(a);
+            let (b_num, b_rest) = split_digits(b);
+            let a_val = a_num.trim_start_matches('0');
+            let b_val = b_num.trim_start_matches('0');
+
+            let ord = a_val
+                .len()
+                .cmp(&b_val.len())
+                .then_with(|| a_val.cmp(b_val))
+                .then_with(|| a_num.len().cmp(&b_num.len()));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+
+            a = a_rest;
+            b = b_rest;
+        } else {
+            if x != y {
+                return x.cmp(&y);
+            }
+
+            a = &a[x.len_utf8()..];
+            b = &b[y.len_utf8()..];
+        }
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_orders_numbers_by_value() {
+        assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_cmp("file10", "file9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a1b2", "a1b10"), Ordering::Less);
+    }
+
+    #[test]
+    fn natural_leading_zeros_break_ties() {
+        assert_eq!(natural_cmp("file007", "file8"), Ordering::Less);
+        assert_eq!(natural_cmp("file01", "file1"), Ordering::Greater);
+        assert_eq!(natural_cmp("file001", "file01"), Ordering::Greater);
+        assert_eq!(natural_cmp("file00", "file0"), Ordering::Greater);
+    }
+
+    #[test]
+    fn natural_prefix_sorts_first() {
+        assert_eq!(natural_cmp("file", "file1"), Ordering::Less);
+        assert_eq!(natural_cmp("x", "x"), Ordering::Equal);
+    }
+}
@@ -0,0 +1,37 @@
+use crate::walker::{Entry, EntryKind};
+
+/// Counts for the report printed after a tree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Directories below the root.
+    pub dirs: usize,
+    /// Everything else below the root, including links that were not
+    /// followed.
+    pub files: usize,
+    /// Total size of the tree, when sizes are enabled.
+    pub size: Option<u64>,
+}
+
+impl Summary {
+    /// Count one entry of a walk. The root only contributes its total size.
+    pub fn add(&mut self, entry: &Entry) {
+        if entry.depth == 0 {
+            if let Some(size) = entry.size {
+                self.size = Some(self.size.unwrap_or(0) + size);
+            }
+        } else if entry.kind == EntryKind::Dir {
+            self.dirs += 1;
+        } else {
+            self.files += 1;
+        }
+    }
+
+    /// Combine the counts of another tree into this one.
+    pub fn merge(&mut self, other: &Summary) {
+        self.dirs += other.dirs;
+        self.files += other.files;
+        if let Some(size) = other.size {
+            self.size = Some(self.size.unwrap_or(0) + size);
+        }
+    }
+}
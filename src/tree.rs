@@ -0,0 +1,161 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::error::FileTreeError;
+use crate::walker::{Entry, EntryKind, Link};
+
+/// An in-memory directory tree built by [`crate::TreeWalker::build`].
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    pub children: Vec<Node>,
+    /// See [`Entry::link`].
+    pub link: Option<Link>,
+    /// See [`Entry::elided`].
+    pub elided: Option<usize>,
+    /// Disk usage in bytes when sizes are enabled; for a directory, the total of
+    /// its own size and everything below it.
+    pub size: Option<u64>,
+    /// See [`Entry::error`].
+    pub error: Option<Arc<FileTreeError>>,
+    counted: bool,
+    executable: Option<bool>,
+}
+
+impl Node {
+    /// Collect a pre-order stream of entries, such as a [`crate::Walk`], into
+    /// a tree. A stream without even a root is [`FileTreeError::Empty`], and
+    /// one with a second root, or with an entry more than one level below
+    /// the one before it, is [`FileTreeError::Malformed`].
+    pub fn from_entries<I>(entries: I) -> Result<Node, FileTreeError>
+    where
+        I: IntoIterator<Item = Result<Entry, FileTreeError>>,
+    {
+        // Entries arrive in pre-order, so the stack holds the chain of open
+        // directories from the root down to the parent of the next entry.
+        let mut stack: Vec<Node> = vec![];
+
+        for entry in entries {
+            let entry = entry?;
+            if entry.depth == 0 && !stack.is_empty() {
+                return Err(FileTreeError::Malformed(format!(
+                    "second root {}",
+                    entry.path.display()
+                )));
+            }
+            if entry.depth > stack.len() {
+                return Err(FileTreeError::Malformed(format!(
+                    "{} at depth {} has no parent",
+                    entry.path.display(),
+                    entry.depth
+                )));
+            }
+            collapse(&mut stack, entry.depth);
+            stack.push(Node {
+                path: entry.path,
+                kind: entry.kind,
+                children: vec![],
+                link: entry.link,
+                elided: entry.elided,
+                size: entry.size,
+                error: entry.error,
+                counted: entry.counted,
+                executable: entry.executable,
+            });
+        }
+
+        collapse(&mut stack, 1);
+        stack.pop().ok_or(FileTreeError::Empty)
+    }
+
+    /// Stream the tree back out as entries in pre-order, with directory
+    /// sizes holding their totals.
+    pub fn entries(&self) -> impl Iterator<Item = Result<Entry, FileTreeError>> + '_ {
+        // (node, depth, parent index, is_last), pushed in reverse so that
+        // siblings pop in display order.
+        let mut stack: Vec<(&Node, usize, Option<usize>, bool)> = vec![(self, 0, None, true)];
+        let mut index = 0;
+
+        std::iter::from_fn(move || {
+            let (node, depth, parent, is_last) = stack.pop()?;
+
+            let last = node.children.len().saturating_sub(1);
+            for (i, child) in node.children.iter().enumerate().rev() {
+                stack.push((child, depth + 1, Some(index), i == last));
+            }
+
+            let entry = Entry {
+                path: node.path.clone(),
+                index,
+                parent,
+                depth,
+                kind: node.kind,
+                is_last,
+                link: node.link.clone(),
+                elided: node.elided,
+                size: node.size,
+                counted: node.counted,
+                executable: node.executable,
+                error: node.error.clone(),
+            };
+            index += 1;
+            Some(Ok(entry))
+        })
+    }
+}
+
+/// Attach every node deeper than `depth` to its parent, adding its size to
+/// the parent's total.
+fn collapse(stack: &mut Vec<Node>, depth: usize) {
+    while stack.len() > depth {
+        let node = stack.pop().unwrap();
+        let parent = stack.last_mut().unwrap();
+        if node.counted {
+            if let (Some(total), Some(size)) = (&mut parent.size, node.size) {
+                *total += size;
+            }
+        }
+        parent.children.push(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeWalker;
+
+    fn walker() -> TreeWalker {
+        TreeWalker::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src"))
+    }
+
+    #[test]
+    fn second_root_is_malformed() {
+        let (first, second) = (walker(), walker());
+        let entries = first.walk().chain(second.walk());
+        assert!(matches!(
+            Node::from_entries(entries),
+            Err(FileTreeError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn skipped_level_is_malformed() {
+        let walker = walker();
+        let entries = walker
+            .walk()
+            .filter(|entry| !matches!(entry, Ok(entry) if entry.depth == 1));
+        assert!(matches!(
+            Node::from_entries(entries),
+            Err(FileTreeError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn empty_stream_is_empty() {
+        assert!(matches!(
+            Node::from_entries(std::iter::empty()),
+            Err(FileTreeError::Empty)
+        ));
+    }
+}
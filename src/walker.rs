@@ -0,0 +1,954 @@
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::error::{FileTreeError, InvalidPathReason};
+use crate::gitignore::IgnoreRules;
+use crate::parallel::{Prefetch, MAX_THREADS};
+use crate::patterns::Patterns;
+use crate::sort::{DirOrder, SortKey, SortOrder};
+use crate::tree::Node;
+
+type SortFn = Arc<dyn Fn(&Path, &Path) -> Ordering + Send + Sync>;
+type FilterFn = Arc<dyn Fn(&Path) -> bool + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Dir,
+    File,
+    /// A symbolic link that is not followed, or whose target is missing.
+    Symlink,
+}
+
+impl EntryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryKind::Dir => "directory",
+            EntryKind::File => "file",
+            EntryKind::Symlink => "symlink",
+        }
+    }
+}
+
+/// Where a symbolic link points.
+#[derive(Debug, Clone)]
+pub struct Link {
+    /// The target as stored in the link, which may be relative.
+    pub target: PathBuf,
+    /// The target does not exist.
+    pub broken: bool,
+}
+
+/// A directory entry found while listing, with its kind taken from the
+/// directory entry's file type. Metadata is only fetched when something asks
+/// for it, and then once.
+#[derive(Debug)]
+pub(crate) struct Child {
+    pub(crate) path: PathBuf,
+    pub(crate) kind: EntryKind,
+    pub(crate) link: Option<Link>,
+    /// Whether [`Child::metadata`] describes a link's target.
+    follow: bool,
+    meta: OnceCell<Option<Metadata>>,
+}
+
+impl Child {
+    /// Classify a directory entry. Only symbolic links cost a system call
+    /// here, to read and check their target.
+    fn new(entry: &DirEntry, follow_links: bool) -> Child {
+        let path = entry.path().to_path_buf();
+        let meta = OnceCell::new();
+
+        if !entry.file_type().is_symlink() {
+            let kind = if entry.file_type().is_dir() {
+                EntryKind::Dir
+            } else {
+                EntryKind::File
+            };
+            return Child {
+                path,
+                kind,
+                link: None,
+                follow: follow_links,
+                meta,
+            };
+        }
+
+        let target = fs::metadata(&path);
+        let link = Link {
+            target: fs::read_link(&path).unwrap_or_default(),
+            broken: target.is_err(),
+        };
+        let kind = match &target {
+            Ok(meta) if follow_links && meta.is_dir() => EntryKind::Dir,
+            Ok(_) if follow_links => EntryKind::File,
+            _ => EntryKind::Symlink,
+        };
+        if let (true, Ok(target)) = (follow_links, target) {
+            let _ = meta.set(Some(target));
+        }
+
+        Child {
+            path,
+            kind,
+            link: Some(link),
+            follow: follow_links,
+            meta,
+        }
+    }
+
+    /// The root of a walk, already known to be a directory.
+    fn root(path: PathBuf, follow_links: bool) -> Child {
+        Child {
+            path,
+            kind: EntryKind::Dir,
+            link: None,
+            follow: follow_links,
+            meta: OnceCell::new(),
+        }
+    }
+
+    /// Metadata for the entry, or for a link's target when links are
+    /// followed and the target exists.
+    pub(crate) fn metadata(&self) -> Option<&Metadata> {
+        self.meta
+            .get_or_init(|| {
+                let meta = if self.follow {
+                    fs::metadata(&self.path)
+                } else {
+                    fs::symlink_metadata(&self.path)
+                };
+                meta.or_else(|_| fs::symlink_metadata(&self.path)).ok()
+            })
+            .as_ref()
+    }
+
+    /// Metadata already read for some other reason, without reading it.
+    fn cached_metadata(&self) -> Option<&Metadata> {
+        self.meta.get().and_then(Option::as_ref)
+    }
+
+    pub(crate) fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+
+    /// Identity of the directory, following links. Loops cannot be detected
+    /// where this is unavailable.
+    #[cfg(unix)]
+    pub(crate) fn dir_id(&self) -> Option<FileId> {
+        use std::os::unix::fs::MetadataExt;
+
+        let meta = self.metadata()?;
+        Some((meta.dev(), meta.ino()))
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn dir_id(&self) -> Option<FileId> {
+        None
+    }
+}
+
+/// How the root path is spelled in the entries of a walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootStyle {
+    /// Exactly as passed to [`TreeWalker::new`].
+    Given,
+    /// Absolute, with links, `.` and `..` resolved.
+    Canonical,
+    /// Relative to the current directory, after canonicalizing.
+    Relative,
+}
+
+/// A single entry produced by a walk, in display order.
+///
+/// The root is yielded first with a depth of 0 and index 0; its children
+/// have depth 1. `parent` is the index of the parent directory's entry.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub index: usize,
+    pub parent: Option<usize>,
+    pub depth: usize,
+    pub kind: EntryKind,
+    pub is_last: bool,
+    /// Set when the entry is a symbolic link. With link following enabled,
+    /// `kind` describes the target.
+    pub link: Option<Link>,
+    /// For a directory at the depth limit, the number of children that were
+    /// not descended into.
+    pub elided: Option<usize>,
+    /// Disk usage in bytes when sizes are enabled. Entries from [`TreeWalker::walk`]
+    /// carry a directory's own size, except at the depth limit where the
+    /// whole subtree is included; [`TreeWalker::build`] fills in totals.
+    pub size: Option<u64>,
+    /// False for the second and later links to the same file, which do not
+    /// count towards directory totals.
+    pub(crate) counted: bool,
+    /// Whether a file has an execute bit set, if its metadata was read
+    /// during the walk.
+    pub(crate) executable: Option<bool>,
+    /// Why a directory's children could not be read, or the loop found when
+    /// following it. The walk carries on with the directory's siblings.
+    pub error: Option<Arc<FileTreeError>>,
+}
+
+impl Entry {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+}
+
+/// Builder for a directory tree walk.
+#[derive(Clone)]
+pub struct TreeWalker {
+    root: PathBuf,
+    ignore_hidden: bool,
+    gitignore: bool,
+    pub(crate) follow_links: bool,
+    sizes: bool,
+    include: Patterns,
+    exclude: Patterns,
+    pub(crate) max_depth: Option<usize>,
+    threads: usize,
+    order: SortOrder,
+    sort: Option<SortFn>,
+    filter: Option<FilterFn>,
+}
+
+impl TreeWalker {
+    pub fn new<P: AsRef<Path>>(root: P) -> TreeWalker {
+        TreeWalker {
+            root: root.as_ref().to_path_buf(),
+            ignore_hidden: false,
+            gitignore: false,
+            follow_links: false,
+            sizes: false,
+            include: Patterns::new(),
+            exclude: Patterns::new(),
+            max_depth: None,
+            threads: 1,
+            order: SortOrder::default(),
+            sort: None,
+            filter: None,
+        }
+    }
+
+    /// Skip files and directories whose name starts with a '.'.
+    pub fn ignore_hidden(mut self, yes: bool) -> TreeWalker {
+        self.ignore_hidden = yes;
+        self
+    }
+
+    /// Skip entries matched by `.gitignore` and `.ignore` files, the
+    /// repository's `.git/info/exclude` and the global git excludes file.
+    /// Ignored directories are not descended into.
+    pub fn gitignore(mut self, yes: bool) -> TreeWalker {
+        self.gitignore = yes;
+        self
+    }
+
+    /// Descend into symbolic links to directories. Links that lead back to
+    /// one of their own ancestors are reported with
+    /// [`FileTreeError::Loop`] instead of being followed.
+    pub fn follow_links(mut self, yes: bool) -> TreeWalker {
+        self.follow_links = yes;
+        self
+    }
+
+    /// Record the size of every entry, as the disk space allocated to it.
+    /// Hard links to the same file only count once towards directory
+    /// totals, so the numbers match `du`.
+    pub fn sizes(mut self, yes: bool) -> TreeWalker {
+        self.sizes = yes;
+        self
+    }
+
+    /// Only show files matching `pattern`, and only the directories leading
+    /// to them. May be given several times; a file matching any is shown.
+    pub fn include(mut self, pattern: &str) -> Result<TreeWalker, FileTreeError> {
+        self.include.add(pattern)?;
+        Ok(self)
+    }
+
+    /// Skip files and directories matching `pattern`. Excluded directories
+    /// are not descended into.
+    pub fn exclude(mut self, pattern: &str) -> Result<TreeWalker, FileTreeError> {
+        self.exclude.add(pattern)?;
+        Ok(self)
+    }
+
+    /// Do not descend below `depth` levels. The root's children are at depth 1.
+    /// Directories at the limit report how many children were cut off in
+    /// [`Entry::elided`].
+    pub fn max_depth(mut self, depth: usize) -> TreeWalker {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Order siblings by `key`. Defaults to [`SortKey::Name`].
+    pub fn sort(mut self, key: SortKey) -> TreeWalker {
+        self.order.key = key;
+        self
+    }
+
+    /// Where directories go among their siblings. Defaults to
+    /// [`DirOrder::First`].
+    pub fn dirs(mut self, order: DirOrder) -> TreeWalker {
+        self.order.dirs = order;
+        self
+    }
+
+    /// Reverse the sort key. Directories stay grouped as set by
+    /// [`TreeWalker::dirs`].
+    pub fn reverse(mut self, yes: bool) -> TreeWalker {
+        self.order.reverse = yes;
+        self
+    }
+
+    /// Compare names and extensions case-insensitively.
+    pub fn ignore_case(mut self, yes: bool) -> TreeWalker {
+        self.order.ignore_case = yes;
+        self
+    }
+
+    /// Order siblings with a custom comparison, overriding the sort settings.
+    pub fn sort_by<F>(mut self, cmp: F) -> TreeWalker
+    where
+        F: Fn(&Path, &Path) -> Ordering + Send + Sync + 'static,
+    {
+        self.sort = Some(Arc::new(cmp));
+        self
+    }
+
+    /// Only yield entries for which `predicate` returns true. Rejected
+    /// directories are not descended into.
+    pub fn filter_entry<P>(mut self, predicate: P) -> TreeWalker
+    where
+        P: Fn(&Path) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Respell the root. A root that cannot be resolved is left as given,
+    /// so that the walk reports why.
+    pub fn root_style(mut self, style: RootStyle) -> TreeWalker {
+        let resolved = match style {
+            RootStyle::Given => return self,
+            RootStyle::Canonical => self.root.canonicalize(),
+            RootStyle::Relative => self.root.canonicalize().and_then(|root| {
+                let cwd = std::env::current_dir()?.canonicalize()?;
+                Ok(relative_to(&root, &cwd))
+            }),
+        };
+        if let Ok(root) = resolved {
+            self.root = root;
+        }
+        self
+    }
+
+    /// Read directories on `threads` threads ahead of the walk. Entries still
+    /// come out in the same order; 1, the default, reads them one at a time
+    /// as the walk reaches them. At most 64 threads are used.
+    pub fn threads(mut self, threads: usize) -> TreeWalker {
+        self.threads = threads.clamp(1, MAX_THREADS);
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Stream the entries of the tree in pre-order, starting with the root.
+    pub fn walk(&self) -> Walk<'_> {
+        let probes = Arc::new(Probes::default());
+        Walk {
+            walker: self,
+            stack: vec![],
+            started: false,
+            count: 0,
+            seen: HashSet::new(),
+            probes: probes.clone(),
+            prefetch: if self.threads > 1 {
+                Prefetch::new(Arc::new(self.clone()), self.threads, probes)
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Walk the whole tree and collect it into memory.
+    pub fn build(&self) -> Result<Node, FileTreeError> {
+        Node::from_entries(self.walk())
+    }
+
+    pub(crate) fn get_dir_entries(
+        &self,
+        path: &Path,
+        rules: Option<&Arc<IgnoreRules>>,
+        probes: &Probes,
+    ) -> Result<Vec<Child>, FileTreeError> {
+        let mut entries = self.list_dir(path, rules, probes)?;
+
+        match &self.sort {
+            Some(cmp) => entries.sort_by(|a, b| cmp(&a.path, &b.path)),
+            None => self.order.sort(&mut entries),
+        }
+
+        Ok(entries)
+    }
+
+    /// The visible children of `path`, in directory order.
+    fn list_dir(
+        &self,
+        path: &Path,
+        rules: Option<&Arc<IgnoreRules>>,
+        probes: &Probes,
+    ) -> Result<Vec<Child>, FileTreeError> {
+        let mut entries: Vec<Child> = vec![];
+
+        for entry in WalkDir::new(path).min_depth(1).max_depth(1) {
+            let entry = entry?;
+
+            if !self.is_visible(&entry, rules) {
+                continue;
+            }
+
+            let child = Child::new(&entry, self.follow_links);
+            if !self.include.is_empty() && !self.is_included(&child, rules, probes) {
+                continue;
+            }
+
+            entries.push(child);
+        }
+
+        Ok(entries)
+    }
+
+    fn is_visible(&self, entry: &DirEntry, rules: Option<&Arc<IgnoreRules>>) -> bool {
+        if self.ignore_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+            return false;
+        }
+
+        // The repository's own database is never worth showing next to the
+        // files it tracks.
+        if self.gitignore && entry.file_name() == ".git" {
+            return false;
+        }
+
+        if let Some(rules) = rules {
+            if rules.is_ignored(Path::new(entry.file_name()), entry.file_type().is_dir()) {
+                return false;
+            }
+        }
+
+        if !self.exclude.is_empty() && self.exclude.is_match(self.relative(entry.path())) {
+            return false;
+        }
+
+        if let Some(filter) = &self.filter {
+            if !filter(entry.path()) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Whether a file matches the include patterns, or a directory has a
+    /// visible descendant that does. The search keeps its own stack so that
+    /// deep trees cannot overflow the call stack, and skips links that lead
+    /// back to a directory already being searched. A directory that cannot
+    /// be read counts as a match, so that the walk reaches it and reports
+    /// why.
+    ///
+    /// What the search learns about the directories below is left in
+    /// `probes` for when the walk lists them, so that a deep match is only
+    /// searched for once rather than again from every directory above it.
+    fn is_included(
+        &self,
+        child: &Child,
+        rules: Option<&Arc<IgnoreRules>>,
+        probes: &Probes,
+    ) -> bool {
+        if !child.is_dir() {
+            return self.include.is_match(self.relative(&child.path));
+        }
+        if let Some(known) = probes.take(&child.path) {
+            return known;
+        }
+
+        let id = |child: &Child| {
+            if self.follow_links {
+                child.dir_id()
+            } else {
+                None
+            }
+        };
+        let descend = |rules: Option<&Arc<IgnoreRules>>, dir: &Path| {
+            rules.map(|rules| rules.descend(Path::new(dir.file_name().unwrap_or_default())))
+        };
+
+        // (directory, its rules, depth below `child`, identity)
+        let mut pending = vec![(
+            child.path.clone(),
+            descend(rules, &child.path),
+            0,
+            id(child),
+        )];
+        // The directories from `child` down to the one being listed.
+        let mut open: Vec<(PathBuf, Option<FileId>)> = vec![];
+        // Directories searched in full without a match, with their depth.
+        // Only those whose parent is still open are kept, as the others will
+        // never be listed.
+        let mut searched: Vec<(PathBuf, usize)> = vec![];
+
+        while let Some((dir, rules, depth, dir_id)) = pending.pop() {
+            while open.len() > depth {
+                let (done, _) = open.pop().unwrap();
+                searched.retain(|&(_, below)| below <= open.len());
+                searched.push((done, open.len()));
+            }
+            if dir_id.is_some() && open.iter().any(|(_, id)| *id == dir_id) {
+                continue;
+            }
+            open.push((dir.clone(), dir_id));
+
+            for entry in WalkDir::new(&dir).min_depth(1).max_depth(1) {
+                let found = match &entry {
+                    Ok(entry) if !self.is_visible(entry, rules.as_ref()) => continue,
+                    Ok(entry) => {
+                        let child = Child::new(entry, self.follow_links);
+                        if child.is_dir() {
+                            let rules = descend(rules.as_ref(), &child.path);
+                            pending.push((child.path.clone(), rules, depth + 1, id(&child)));
+                            continue;
+                        }
+                        self.include.is_match(self.relative(&child.path))
+                    }
+                    Err(_) => true,
+                };
+
+                if found {
+                    probes.record(open.into_iter().skip(1).map(|(dir, _)| (dir, true)));
+                    probes.record(searched.into_iter().map(|(dir, _)| (dir, false)));
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+}
+
+/// Results of include searches for directories a walk has yet to list.
+#[derive(Default)]
+pub(crate) struct Probes(Mutex<HashMap<PathBuf, bool>>);
+
+impl Probes {
+    fn take(&self, dir: &Path) -> Option<bool> {
+        self.0.lock().unwrap().remove(dir)
+    }
+
+    fn record(&self, dirs: impl Iterator<Item = (PathBuf, bool)>) {
+        self.0.lock().unwrap().extend(dirs);
+    }
+}
+
+/// The remaining children of a directory being walked.
+struct Frame {
+    index: usize,
+    dir: PathBuf,
+    id: Option<FileId>,
+    rules: Option<Arc<IgnoreRules>>,
+    children: std::vec::IntoIter<Child>,
+}
+
+/// Iterator over the entries of a [`TreeWalker`].
+pub struct Walk<'a> {
+    walker: &'a TreeWalker,
+    stack: Vec<Frame>,
+    started: bool,
+    count: usize,
+    seen: HashSet<FileId>,
+    probes: Arc<Probes>,
+    prefetch: Option<Prefetch>,
+}
+
+impl Walk<'_> {
+    /// Ignore rules for a directory about to be listed: the root's are loaded
+    /// from its ancestors, every other directory extends its parent's, whose
+    /// frame is on top of the stack.
+    fn rules_for(&self, dir: &Path) -> Option<Arc<IgnoreRules>> {
+        if !self.walker.gitignore {
+            return None;
+        }
+
+        match self.stack.last() {
+            Some(frame) => frame
+                .rules
+                .as_ref()
+                .map(|rules| rules.descend(Path::new(dir.file_name().unwrap_or_default()))),
+            None => {
+                let root = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+                Some(IgnoreRules::for_root(&root))
+            }
+        }
+    }
+
+    /// Build the entry for `child`. A directory's children are read right
+    /// away so that a failure can be reported on the directory itself; they
+    /// are either pushed to be walked next or, at the depth limit, counted.
+    fn visit(&mut self, child: Child, parent: Option<usize>, depth: usize, is_last: bool) -> Entry {
+        let index = self.count;
+        self.count += 1;
+
+        let (mut size, counted) = self.measure(&child);
+        let executable = child.cached_metadata().map(is_executable);
+        let mut elided = None;
+        let mut error = None;
+        let path = &child.path;
+
+        if child.is_dir() {
+            let id = if self.walker.follow_links {
+                child.dir_id()
+            } else {
+                None
+            };
+            let ancestor = id.and_then(|id| self.stack.iter().find(|frame| frame.id == Some(id)));
+
+            if let Some(ancestor) = ancestor {
+                error = Some(Arc::new(FileTreeError::Loop {
+                    path: path.clone(),
+                    ancestor: ancestor.dir.clone(),
+                }));
+            } else if self.descends(depth) {
+                let (rules, children) = self.read_dir(path, depth, id);
+                match children {
+                    Ok(children) => self.stack.push(Frame {
+                        index,
+                        dir: path.clone(),
+                        id,
+                        rules,
+                        children: children.into_iter(),
+                    }),
+                    Err(err) => error = Some(Arc::new(err)),
+                }
+            } else {
+                let rules = self.rules_for(path);
+                match self.walker.list_dir(path, rules.as_ref(), &self.probes) {
+                    Ok(children) if !children.is_empty() => elided = Some(children.len()),
+                    Ok(_) => {}
+                    Err(err) => error = Some(Arc::new(err)),
+                }
+                if let Some(own) = size {
+                    let (below, failed) = self.subtree_size(path, id, rules);
+                    size = Some(own + below);
+                    if let (None, Some(err)) = (&error, failed) {
+                        error = Some(Arc::new(err));
+                    }
+                }
+            }
+        }
+
+        Entry {
+            path: child.path,
+            index,
+            parent,
+            depth,
+            kind: child.kind,
+            is_last,
+            link: child.link,
+            elided,
+            size,
+            counted,
+            executable,
+            error,
+        }
+    }
+
+    /// The sorted children of a directory being descended into, and the
+    /// ignore rules for them. They come from the prefetch pool if it has
+    /// read the directory; otherwise they are read here and the pool reads
+    /// ahead from them.
+    fn read_dir(
+        &self,
+        dir: &Path,
+        depth: usize,
+        id: Option<FileId>,
+    ) -> (Option<Arc<IgnoreRules>>, Result<Vec<Child>, FileTreeError>) {
+        if let Some(listing) = self.prefetch.as_ref().and_then(|pool| pool.take(dir)) {
+            return (listing.rules, listing.children);
+        }
+
+        let rules = self.rules_for(dir);
+        let children = self
+            .walker
+            .get_dir_entries(dir, rules.as_ref(), &self.probes);
+        if let (Some(pool), Ok(children)) = (&self.prefetch, &children) {
+            let ancestors = self.stack.iter().filter_map(|frame| frame.id).chain(id);
+            pool.queue(depth, rules.as_ref(), ancestors.collect(), children);
+        }
+        (rules, children)
+    }
+
+    fn descends(&self, depth: usize) -> bool {
+        self.walker.max_depth.is_none_or(|max| depth < max)
+    }
+
+    /// The size of an entry and whether it counts towards totals, or
+    /// `(None, true)` when sizes are disabled or unavailable.
+    fn measure(&mut self, child: &Child) -> (Option<u64>, bool) {
+        if !self.walker.sizes {
+            return (None, true);
+        }
+
+        match child.metadata() {
+            Some(meta) => (Some(disk_usage(meta)), self.first_link(meta)),
+            None => (None, true),
+        }
+    }
+
+    /// Total size of everything visible below `dir`, for directories that
+    /// are not descended into, and the first error from a directory below
+    /// that could not be read and so is missing from the total. Links are
+    /// followed as the walk would, stopping at any that lead back to a
+    /// directory above, so totals do not depend on the depth limit.
+    fn subtree_size(
+        &mut self,
+        dir: &Path,
+        id: Option<FileId>,
+        rules: Option<Arc<IgnoreRules>>,
+    ) -> (u64, Option<FileTreeError>) {
+        let ancestors: Vec<FileId> = self
+            .stack
+            .iter()
+            .filter_map(|frame| frame.id)
+            .chain(id)
+            .collect();
+        let mut total = 0;
+        let mut failed = None;
+        let mut pending = vec![(dir.to_path_buf(), rules, Arc::new(ancestors))];
+
+        while let Some((dir, rules, ancestors)) = pending.pop() {
+            let children = match self.walker.list_dir(&dir, rules.as_ref(), &self.probes) {
+                Ok(children) => children,
+                Err(err) => {
+                    failed.get_or_insert(err);
+                    continue;
+                }
+            };
+
+            for child in children {
+                if let Some(meta) = child.metadata() {
+                    if self.first_link(meta) {
+                        total += disk_usage(meta);
+                    }
+                }
+
+                // Without metadata, go by the directory entry, so that the
+                // listing fails and says why.
+                let is_dir = match child.metadata() {
+                    Some(meta) => meta.is_dir(),
+                    None => child.link.is_none() && child.is_dir(),
+                };
+                if !is_dir {
+                    continue;
+                }
+
+                let mut ancestors = ancestors.clone();
+                if self.walker.follow_links {
+                    match child.dir_id() {
+                        Some(id) if ancestors.contains(&id) => continue,
+                        Some(id) => Arc::make_mut(&mut ancestors).push(id),
+                        None => {}
+                    }
+                }
+
+                let rules = rules.as_ref().map(|rules| {
+                    rules.descend(Path::new(child.path.file_name().unwrap_or_default()))
+                });
+                pending.push((child.path, rules, ancestors));
+            }
+        }
+
+        (total, failed)
+    }
+
+    fn first_link(&mut self, meta: &Metadata) -> bool {
+        match file_id(meta) {
+            Some(id) => self.seen.insert(id),
+            None => true,
+        }
+    }
+}
+
+/// Device and inode number.
+pub(crate) type FileId = (u64, u64);
+
+/// Identity of a file with more than one hard link.
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+
+    if meta.is_dir() || meta.nlink() < 2 {
+        return None;
+    }
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<FileId> {
+    None
+}
+
+/// Bytes of disk allocated to a file, which is what `du` counts. Sparse
+/// files use less than their length and small files a whole block.
+#[cfg(unix)]
+fn disk_usage(meta: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    meta.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn disk_usage(meta: &Metadata) -> u64 {
+    meta.len()
+}
+
+#[cfg(unix)]
+pub(crate) fn is_executable(meta: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+pub(crate) fn is_executable(_meta: &Metadata) -> bool {
+    false
+}
+
+/// `path` relative to `base`, both absolute, climbing out of `base` with
+/// `..` as needed.
+pub(crate) fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut rel = PathBuf::new();
+    for _ in base.components().skip(common) {
+        rel.push("..");
+    }
+    rel.extend(path.components().skip(common));
+
+    if rel.as_os_str().is_empty() {
+        rel.push(".");
+    }
+    rel
+}
+
+fn check_root(root: &Path) -> Result<(), FileTreeError> {
+    let reason = match fs::metadata(root) {
+        Ok(meta) if meta.is_dir() => return Ok(()),
+        Ok(_) => InvalidPathReason::NotADirectory,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => InvalidPathReason::NotFound,
+        Err(_) => InvalidPathReason::Inaccessible,
+    };
+
+    Err(FileTreeError::InvalidPath {
+        path: root.to_path_buf(),
+        reason,
+    })
+}
+
+impl Iterator for Walk<'_> {
+    type Item = Result<Entry, FileTreeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            let root = Child::root(self.walker.root.clone(), self.walker.follow_links);
+            if let Err(err) = check_root(&root.path) {
+                return Some(Err(err));
+            }
+            return Some(Ok(self.visit(root, None, 0, true)));
+        }
+
+        loop {
+            let depth = self.stack.len();
+            let frame = self.stack.last_mut()?;
+            let Some(child) = frame.children.next() else {
+                self.stack.pop();
+                continue;
+            };
+
+            let is_last = frame.children.len() == 0;
+            let parent = frame.index;
+            return Some(Ok(self.visit(child, Some(parent), depth, is_last)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn followed_link_totals_do_not_depend_on_depth() {
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join(format!("treewalker-totals-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("real/sub")).unwrap();
+        fs::create_dir_all(dir.join("other")).unwrap();
+        fs::write(dir.join("real/sub/big"), vec![0; 5000]).unwrap();
+        fs::write(dir.join("other/x"), vec![0; 300]).unwrap();
+        symlink("../other", dir.join("real/linked")).unwrap();
+        symlink("..", dir.join("real/sub/loop")).unwrap();
+
+        let total = |depth: Option<usize>| {
+            let mut walker = TreeWalker::new(&dir).sizes(true).follow_links(true);
+            if let Some(depth) = depth {
+                walker = walker.max_depth(depth);
+            }
+            walker.build().unwrap().size.unwrap()
+        };
+        let full = total(None);
+        for depth in 1..4 {
+            assert_eq!(total(Some(depth)), full, "max depth {}", depth);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn relative_to_climbs_out_of_base() {
+        let rel = |path: &str, base: &str| relative_to(Path::new(path), Path::new(base));
+
+        assert_eq!(rel("/a/b/c", "/a/b"), Path::new("c"));
+        assert_eq!(rel("/a/b", "/a/b/c/d"), Path::new("../.."));
+        assert_eq!(rel("/a/x/y", "/a/b/c"), Path::new("../../x/y"));
+        assert_eq!(rel("/x", "/a/b"), Path::new("../../x"));
+    }
+
+    #[test]
+    fn relative_to_itself_is_dot() {
+        assert_eq!(
+            relative_to(Path::new("/a/b"), Path::new("/a/b")),
+            Path::new(".")
+        );
+        assert_eq!(relative_to(Path::new("/"), Path::new("/")), Path::new("."));
+    }
+}